@@ -15,21 +15,30 @@ pub struct AccountProfileResponse {
     pub created: String,
 }
 
+#[cfg(feature = "chrono")]
+impl AccountProfileResponse {
+    /// When the account was created.
+    pub fn created_utc(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        crate::timestamp::parse(&self.created)
+    }
+}
+
 #[async_trait]
 impl Account for ShodanClient {
     async fn get_account_profile(&self) -> Result<AccountProfileResponse, Error> {
-        Self::fetch(self.build_request_url("/account/profile", &Default::default())?).await
+        self.fetch(self.build_request_url("/account/profile", &Default::default())?)
+            .await
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::tests::get_test_api_key;
+    use crate::tests::test_client;
     use crate::*;
 
     #[tokio::test]
     async fn can_get_account_profile() {
-        let client = ShodanClient::new(get_test_api_key());
+        let client = test_client().await;
         client.get_account_profile().await.unwrap();
     }
 }
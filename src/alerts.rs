@@ -0,0 +1,546 @@
+use crate::*;
+use async_trait::async_trait;
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+
+#[async_trait]
+pub trait Alerts {
+    /// Creates a network alert for the given IPs and netblocks. `expires` is the amount of
+    /// seconds the alert should stay active for, it never expires when omitted.
+    async fn create_alert(
+        &self,
+        name: String,
+        filters: AlertFilters,
+        expires: Option<u32>,
+    ) -> Result<Alert, Error>;
+
+    async fn get_alert(&self, id: String) -> Result<Alert, Error>;
+
+    async fn get_alerts(&self) -> Result<Vec<Alert>, Error>;
+
+    /// Replaces the IPs and netblocks monitored by the alert.
+    async fn edit_alert(&self, id: String, filters: AlertFilters) -> Result<Alert, Error>;
+
+    async fn delete_alert(&self, id: String) -> Result<(), Error>;
+
+    /// Lists the triggers that can be enabled on alerts.
+    async fn get_alert_triggers(&self) -> Result<Vec<Trigger>, Error>;
+
+    async fn enable_alert_trigger(&self, id: String, trigger: String) -> Result<(), Error>;
+
+    async fn disable_alert_trigger(&self, id: String, trigger: String) -> Result<(), Error>;
+
+    /// Stops the trigger from firing for a service, `service` is formatted as `ip:port`.
+    async fn ignore_alert_trigger_service(
+        &self,
+        id: String,
+        trigger: String,
+        service: String,
+    ) -> Result<(), Error>;
+
+    /// Undoes `ignore_alert_trigger_service`.
+    async fn unignore_alert_trigger_service(
+        &self,
+        id: String,
+        trigger: String,
+        service: String,
+    ) -> Result<(), Error>;
+
+    async fn add_alert_notifier(&self, id: String, notifier_id: String) -> Result<(), Error>;
+
+    async fn remove_alert_notifier(&self, id: String, notifier_id: String) -> Result<(), Error>;
+
+    /// Provisions an alert in one go: creates the notifier and the alert, enables the triggers
+    /// and attaches the notifier. Returns the alert as it looks after all of that. When any
+    /// step fails the notifier and alert created so far are deleted again on a best-effort
+    /// basis before the error is returned.
+    async fn create_alert_with_notifier(
+        &self,
+        name: String,
+        filters: AlertFilters,
+        triggers: Vec<String>,
+        notifier: NotifierArgs,
+    ) -> Result<Alert, Error>;
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct AlertFilters {
+    pub ip: Vec<String>,
+}
+
+impl AlertFilters {
+    /// Adds an IP or netblock (in CIDR notation) to monitor.
+    pub fn ip(mut self, ip: impl ToString) -> Self {
+        self.ip.push(ip.to_string());
+
+        self
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Alert {
+    pub id: String,
+    pub name: String,
+    pub created: String,
+    pub expires: u32,
+    pub expiration: Option<String>,
+    pub filters: AlertFilters,
+    pub size: u32,
+    #[serde(default)]
+    pub has_triggers: bool,
+    #[serde(default)]
+    pub triggers: HashMap<String, AlertTrigger>,
+    #[serde(default)]
+    pub notifiers: Vec<Notifier>,
+}
+
+#[cfg(feature = "chrono")]
+impl Alert {
+    /// When the alert was created.
+    pub fn created_utc(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        crate::timestamp::parse(&self.created)
+    }
+
+    /// When the alert expires, if ever.
+    pub fn expiration_utc(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        self.expiration.as_deref().and_then(crate::timestamp::parse)
+    }
+}
+
+/// A trigger enabled on an alert.
+#[derive(Deserialize, Debug)]
+pub struct AlertTrigger {
+    /// Services, formatted as `ip:port`, the trigger doesn't fire for.
+    #[serde(default)]
+    pub ignore: Vec<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Trigger {
+    pub name: String,
+    pub description: String,
+    pub rule: String,
+}
+
+#[async_trait]
+impl Alerts for ShodanClient {
+    async fn create_alert(
+        &self,
+        name: String,
+        filters: AlertFilters,
+        expires: Option<u32>,
+    ) -> Result<Alert, Error> {
+        let mut body = serde_json::json!({
+            "name": name,
+            "filters": filters,
+        });
+        if let Some(expires) = expires {
+            body["expires"] = expires.into();
+        }
+
+        self.send(
+            Method::POST,
+            self.build_request_url("/shodan/alert", &Default::default())?,
+            Some(RequestBody::Json(body)),
+            CallKind::Create,
+        )
+        .await
+    }
+
+    async fn get_alert(&self, id: String) -> Result<Alert, Error> {
+        self.fetch(self.build_request_url(
+            format!("/shodan/alert/{id}/info").as_str(),
+            &Default::default(),
+        )?)
+        .await
+    }
+
+    async fn get_alerts(&self) -> Result<Vec<Alert>, Error> {
+        self.fetch(self.build_request_url("/shodan/alert/info", &Default::default())?)
+            .await
+    }
+
+    async fn edit_alert(&self, id: String, filters: AlertFilters) -> Result<Alert, Error> {
+        self.send(
+            Method::POST,
+            self.build_request_url(format!("/shodan/alert/{id}").as_str(), &Default::default())?,
+            Some(RequestBody::Json(serde_json::json!({ "filters": filters }))),
+            CallKind::Create,
+        )
+        .await
+    }
+
+    async fn delete_alert(&self, id: String) -> Result<(), Error> {
+        self.perform(
+            Method::DELETE,
+            self.build_request_url(format!("/shodan/alert/{id}").as_str(), &Default::default())?,
+            None,
+        )
+        .await
+    }
+
+    async fn get_alert_triggers(&self) -> Result<Vec<Trigger>, Error> {
+        self.fetch(self.build_request_url("/shodan/alert/triggers", &Default::default())?)
+            .await
+    }
+
+    async fn enable_alert_trigger(&self, id: String, trigger: String) -> Result<(), Error> {
+        self.perform(
+            Method::PUT,
+            self.build_request_url(
+                format!("/shodan/alert/{id}/trigger/{trigger}").as_str(),
+                &Default::default(),
+            )?,
+            None,
+        )
+        .await
+    }
+
+    async fn disable_alert_trigger(&self, id: String, trigger: String) -> Result<(), Error> {
+        self.perform(
+            Method::DELETE,
+            self.build_request_url(
+                format!("/shodan/alert/{id}/trigger/{trigger}").as_str(),
+                &Default::default(),
+            )?,
+            None,
+        )
+        .await
+    }
+
+    async fn ignore_alert_trigger_service(
+        &self,
+        id: String,
+        trigger: String,
+        service: String,
+    ) -> Result<(), Error> {
+        self.perform(
+            Method::PUT,
+            self.build_request_url(
+                format!("/shodan/alert/{id}/trigger/{trigger}/ignore/{service}").as_str(),
+                &Default::default(),
+            )?,
+            None,
+        )
+        .await
+    }
+
+    async fn unignore_alert_trigger_service(
+        &self,
+        id: String,
+        trigger: String,
+        service: String,
+    ) -> Result<(), Error> {
+        self.perform(
+            Method::DELETE,
+            self.build_request_url(
+                format!("/shodan/alert/{id}/trigger/{trigger}/ignore/{service}").as_str(),
+                &Default::default(),
+            )?,
+            None,
+        )
+        .await
+    }
+
+    async fn add_alert_notifier(&self, id: String, notifier_id: String) -> Result<(), Error> {
+        self.perform(
+            Method::PUT,
+            self.build_request_url(
+                format!("/shodan/alert/{id}/notifier/{notifier_id}").as_str(),
+                &Default::default(),
+            )?,
+            None,
+        )
+        .await
+    }
+
+    async fn remove_alert_notifier(&self, id: String, notifier_id: String) -> Result<(), Error> {
+        self.perform(
+            Method::DELETE,
+            self.build_request_url(
+                format!("/shodan/alert/{id}/notifier/{notifier_id}").as_str(),
+                &Default::default(),
+            )?,
+            None,
+        )
+        .await
+    }
+
+    async fn create_alert_with_notifier(
+        &self,
+        name: String,
+        filters: AlertFilters,
+        triggers: Vec<String>,
+        notifier: NotifierArgs,
+    ) -> Result<Alert, Error> {
+        let notifier_id = self.create_notifier(notifier, Some(name.clone())).await?;
+        let alert = match self.create_alert(name, filters, None).await {
+            Ok(alert) => alert,
+            Err(e) => {
+                let _ = self.delete_notifier(notifier_id).await;
+                return Err(e);
+            }
+        };
+
+        let provisioned = async {
+            if !triggers.is_empty() {
+                self.enable_alert_trigger(alert.id.clone(), triggers.join(","))
+                    .await?;
+            }
+            self.add_alert_notifier(alert.id.clone(), notifier_id.clone())
+                .await?;
+
+            self.get_alert(alert.id.clone()).await
+        }
+        .await;
+
+        if provisioned.is_err() {
+            let _ = self.delete_alert(alert.id).await;
+            let _ = self.delete_notifier(notifier_id).await;
+        }
+
+        provisioned
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::{MockResponse, MockServer};
+    use crate::tests::test_client;
+    use crate::*;
+    use reqwest::Method;
+
+    const ALERT_ID: &str = "HKVGAIRWD79Z7W2T";
+
+    #[tokio::test]
+    async fn can_create_alert() {
+        let server = MockServer::with_fixtures().await;
+        let alert = server
+            .client()
+            .create_alert(
+                String::from("DNS Alert"),
+                AlertFilters::default().ip("198.20.88.0/24"),
+                Some(3600),
+            )
+            .await
+            .unwrap();
+        assert_eq!(alert.id, ALERT_ID);
+
+        let request = &server.received_requests()[0];
+        let body = serde_json::from_slice::<serde_json::Value>(&request.body).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "name": "DNS Alert",
+                "filters": {"ip": ["198.20.88.0/24"]},
+                "expires": 3600,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn can_get_alerts() {
+        let client = test_client().await;
+        client.get_alerts().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn can_get_alert_triggers() {
+        let client = test_client().await;
+        client.get_alert_triggers().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn can_get_alert() {
+        let server = MockServer::with_fixtures().await;
+        let alert = server
+            .client()
+            .get_alert(String::from(ALERT_ID))
+            .await
+            .unwrap();
+
+        assert_eq!(alert.filters.ip, vec!["198.20.88.0/24"]);
+        assert_eq!(alert.triggers["malware"].ignore, vec!["198.20.88.14:22"]);
+        assert_eq!(alert.notifiers[0].provider, "email");
+    }
+
+    #[tokio::test]
+    async fn can_edit_alert() {
+        let server = MockServer::with_fixtures().await;
+        server
+            .client()
+            .edit_alert(
+                String::from(ALERT_ID),
+                AlertFilters::default().ip("198.20.88.0/24"),
+            )
+            .await
+            .unwrap();
+
+        let request = &server.received_requests()[0];
+        assert_eq!(request.method, Method::POST);
+        assert_eq!(request.path, format!("/shodan/alert/{ALERT_ID}"));
+    }
+
+    #[tokio::test]
+    async fn can_create_alert_with_notifier() {
+        let server = MockServer::with_fixtures().await;
+        let alert = server
+            .client()
+            .create_alert_with_notifier(
+                String::from("DNS Alert"),
+                AlertFilters::default().ip("198.20.88.0/24"),
+                vec![String::from("malware")],
+                NotifierArgs::Email {
+                    to: String::from("security@example.com"),
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(alert.id, ALERT_ID);
+
+        let requests = server
+            .received_requests()
+            .into_iter()
+            .map(|request| (request.method, request.path))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            requests,
+            vec![
+                (Method::POST, String::from("/notifier")),
+                (Method::POST, String::from("/shodan/alert")),
+                (
+                    Method::PUT,
+                    format!("/shodan/alert/{ALERT_ID}/trigger/malware")
+                ),
+                (
+                    Method::PUT,
+                    format!("/shodan/alert/{ALERT_ID}/notifier/7xkHQ2SNS4xmH3Ka")
+                ),
+                (Method::GET, format!("/shodan/alert/{ALERT_ID}/info")),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn doesnt_retry_alert_creation() {
+        let server = MockServer::with_fixtures().await;
+        server.mock_once(
+            Method::POST,
+            "/shodan/alert",
+            MockResponse::error(reqwest::StatusCode::BAD_GATEWAY, "Bad gateway"),
+        );
+
+        let client = ShodanClient::builder(testing::MOCK_API_KEY)
+            .base_url(server.url())
+            .retry_policy(RetryPolicy::default().initial_backoff(std::time::Duration::ZERO))
+            .build()
+            .unwrap();
+
+        let error = client
+            .create_alert(
+                String::from("DNS Alert"),
+                AlertFilters::default().ip("198.20.88.0/24"),
+                None,
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(error, Error::Server(_)));
+        assert_eq!(server.received_requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn cleans_up_failed_alert_provisioning() {
+        let server = MockServer::with_fixtures().await;
+        server.mock(
+            Method::PUT,
+            format!("/shodan/alert/{ALERT_ID}/trigger/malware"),
+            MockResponse::error(reqwest::StatusCode::BAD_REQUEST, "Invalid trigger"),
+        );
+
+        let error = server
+            .client()
+            .create_alert_with_notifier(
+                String::from("DNS Alert"),
+                AlertFilters::default().ip("198.20.88.0/24"),
+                vec![String::from("malware")],
+                NotifierArgs::Email {
+                    to: String::from("security@example.com"),
+                },
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(error, Error::InvalidQuery(_)));
+
+        let requests = server
+            .received_requests()
+            .into_iter()
+            .map(|request| (request.method, request.path))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            requests[3..],
+            [
+                (Method::DELETE, format!("/shodan/alert/{ALERT_ID}")),
+                (Method::DELETE, String::from("/notifier/7xkHQ2SNS4xmH3Ka")),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn can_manage_alert() {
+        let server = MockServer::with_fixtures().await;
+        let client = server.client();
+        let id = String::from(ALERT_ID);
+
+        client
+            .enable_alert_trigger(id.clone(), String::from("malware"))
+            .await
+            .unwrap();
+        client
+            .ignore_alert_trigger_service(
+                id.clone(),
+                String::from("malware"),
+                String::from("198.20.88.14:22"),
+            )
+            .await
+            .unwrap();
+        client
+            .unignore_alert_trigger_service(
+                id.clone(),
+                String::from("malware"),
+                String::from("198.20.88.14:22"),
+            )
+            .await
+            .unwrap();
+        client
+            .disable_alert_trigger(id.clone(), String::from("malware"))
+            .await
+            .unwrap();
+        client
+            .add_alert_notifier(id.clone(), String::from("default"))
+            .await
+            .unwrap();
+        client
+            .remove_alert_notifier(id.clone(), String::from("default"))
+            .await
+            .unwrap();
+        client.delete_alert(id).await.unwrap();
+
+        let methods = server
+            .received_requests()
+            .into_iter()
+            .map(|request| request.method)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            methods,
+            vec![
+                Method::PUT,
+                Method::PUT,
+                Method::DELETE,
+                Method::DELETE,
+                Method::PUT,
+                Method::DELETE,
+                Method::DELETE
+            ]
+        );
+    }
+}
@@ -30,18 +30,19 @@ pub trait ApiInfo {
 #[async_trait]
 impl ApiInfo for ShodanClient {
     async fn get_api_info(&self) -> Result<ApiInfoResponse, Error> {
-        Self::fetch(self.build_request_url("/api-info", &Default::default())?).await
+        self.fetch(self.build_request_url("/api-info", &Default::default())?)
+            .await
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::tests::get_test_api_key;
+    use crate::tests::test_client;
     use crate::*;
 
     #[tokio::test]
     async fn can_get_api_info() {
-        let client = ShodanClient::new(get_test_api_key());
+        let client = test_client().await;
         client.get_api_info().await.unwrap();
     }
 }
@@ -1,4 +1,270 @@
-use std::collections::HashMap;
+use crate::rate_limit::RateLimiter;
+use crate::{
+    Error, FacetName, IpNet, Query, QueryValidator, RateLimit, RecordingTransport,
+    ReqwestTransport, RetryPolicy, ShodanClient, Transport, BASE_API_URL, BASE_STREAM_URL,
+};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use url::Url;
+
+pub struct ShodanClientBuilder {
+    api_key: String,
+    base_url: String,
+    stream_url: String,
+    http_client: Option<reqwest::Client>,
+    user_agent: Option<String>,
+    timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    proxy: Option<reqwest::Proxy>,
+    transport: Option<Arc<dyn Transport>>,
+    record: Option<PathBuf>,
+    rate_limit: Option<RateLimit>,
+    retry_policy: Option<RetryPolicy>,
+    validate_queries: bool,
+    query_validator: Option<QueryValidator>,
+}
+
+impl ShodanClientBuilder {
+    pub fn new(api_key: impl Into<String>) -> Self {
+        Self {
+            api_key: api_key.into(),
+            base_url: BASE_API_URL.into(),
+            stream_url: BASE_STREAM_URL.into(),
+            http_client: None,
+            user_agent: None,
+            timeout: None,
+            connect_timeout: None,
+            proxy: None,
+            transport: None,
+            record: None,
+            rate_limit: None,
+            retry_policy: None,
+            validate_queries: false,
+            query_validator: None,
+        }
+    }
+
+    pub fn build(self) -> Result<ShodanClient, Error> {
+        let base_url = Url::parse(self.base_url.as_str())?;
+        let stream_url = Url::parse(self.stream_url.as_str())?;
+
+        // An injected client is used as-is, the transport options only apply to our own clients.
+        // Streams and bulk downloads stay open far longer than any sensible request timeout, so
+        // they get a client of their own without one.
+        let (http_client, long_lived_client) = match self.http_client {
+            Some(http_client) => (http_client.clone(), http_client),
+            None => {
+                let builder = || {
+                    let mut builder = reqwest::Client::builder();
+                    if let Some(user_agent) = &self.user_agent {
+                        builder = builder.user_agent(user_agent);
+                    }
+                    if let Some(connect_timeout) = self.connect_timeout {
+                        builder = builder.connect_timeout(connect_timeout);
+                    }
+                    if let Some(proxy) = &self.proxy {
+                        builder = builder.proxy(proxy.clone());
+                    }
+
+                    builder
+                };
+
+                let mut http_client = builder();
+                if let Some(timeout) = self.timeout {
+                    http_client = http_client.timeout(timeout);
+                }
+
+                (http_client.build()?, builder().build()?)
+            }
+        };
+
+        let mut transport = self
+            .transport
+            .unwrap_or_else(|| Arc::new(ReqwestTransport::new(http_client)));
+        if let Some(path) = self.record {
+            transport = Arc::new(RecordingTransport::wrap_shared(transport, path));
+        }
+
+        Ok(ShodanClient {
+            api_key: self.api_key,
+            base_url,
+            stream_url,
+            http_client: long_lived_client,
+            transport,
+            rate_limiter: self.rate_limit.map(RateLimiter::new),
+            retry_policy: self.retry_policy,
+            query_validator: match self.query_validator {
+                Some(validator) => Some(Arc::new(validator.into())),
+                None => self.validate_queries.then(Default::default),
+            },
+        })
+    }
+
+    /// Points the client at a different API root, ex: a local mock or an egress gateway.
+    pub fn base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into();
+
+        self
+    }
+
+    /// Points the client at a different streaming API root.
+    pub fn stream_url(mut self, stream_url: impl Into<String>) -> Self {
+        self.stream_url = stream_url.into();
+
+        self
+    }
+
+    /// Uses an existing reqwest client. The user agent, timeouts and proxy set on this builder
+    /// are ignored in that case.
+    pub fn http_client(mut self, http_client: reqwest::Client) -> Self {
+        self.http_client = Some(http_client);
+
+        self
+    }
+
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = Some(user_agent.into());
+
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+
+        self
+    }
+
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+
+        self
+    }
+
+    pub fn proxy(mut self, proxy: reqwest::Proxy) -> Self {
+        self.proxy = Some(proxy);
+
+        self
+    }
+
+    /// Swaps out how requests are sent, ex: to record or replay API interactions with
+    /// `RecordingTransport` and `ReplayTransport`. Bulk data downloads bypass the transport.
+    pub fn transport(mut self, transport: impl Transport + 'static) -> Self {
+        self.transport = Some(Arc::new(transport));
+
+        self
+    }
+
+    /// Writes every request/response pair to a cassette file at `path`, see
+    /// `RecordingTransport`. Calls still go out over the configured transport and HTTP client.
+    pub fn record(mut self, path: impl Into<PathBuf>) -> Self {
+        self.record = Some(path.into());
+
+        self
+    }
+
+    /// Throttles all calls made through the client, and any of its clones, to the given rate.
+    /// Shodan allows 1 request per second which is what `RateLimit::default()` gives you.
+    pub fn rate_limit(mut self, rate_limit: RateLimit) -> Self {
+        self.rate_limit = Some(rate_limit);
+
+        self
+    }
+
+    /// Retries transient failures (connection errors, timeouts, rate limiting and 5xx responses)
+    /// according to the given policy. Failed calls are returned immediately when this isn't set.
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(retry_policy);
+
+        self
+    }
+
+    /// Checks search queries with a `QueryValidator` before they're sent, failing with
+    /// `Error::QueryValidation` instead of spending credits on them. The filters and plan are
+    /// looked up on the first search and cached from then on.
+    pub fn validate_queries(mut self, validate_queries: bool) -> Self {
+        self.validate_queries = validate_queries;
+
+        self
+    }
+
+    /// Checks search queries with the given validator instead of looking one up, ex: to
+    /// `restrict` more filters than `QueryValidator::fetch` does.
+    pub fn query_validator(mut self, validator: QueryValidator) -> Self {
+        self.query_validator = Some(validator);
+
+        self
+    }
+}
+
+/// Builds the `facets` parameter of searches and counts, ex: `country:10,port:50,org`.
+#[derive(Default)]
+pub struct FacetsBuilder {
+    facets: Vec<(FacetName, Option<u32>)>,
+}
+
+impl FacetsBuilder {
+    pub fn build(self) -> String {
+        self.facets
+            .iter()
+            .map(|(name, size)| match size {
+                Some(size) => format!("{name}:{size}"),
+                None => name.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    /// Adds a facet with shodan's default amount of values.
+    pub fn facet(mut self, name: impl Into<FacetName>) -> Self {
+        self.facets.push((name.into(), None));
+
+        self
+    }
+
+    /// Adds a facet that summarizes the results into at most `size` values.
+    pub fn facet_with_size(mut self, name: impl Into<FacetName>, size: u32) -> Self {
+        self.facets.push((name.into(), Some(size)));
+
+        self
+    }
+
+    /// Checks the facets against the ones shodan supports, as returned by `host_facets`.
+    pub fn validate(self, available: &[String]) -> Result<Self, Error> {
+        if let Some((name, _)) = self
+            .facets
+            .iter()
+            .find(|(name, _)| !available.iter().any(|facet| facet == name.as_str()))
+        {
+            return Err(Error::UnknownFacet(name.to_string()));
+        }
+
+        Ok(self)
+    }
+}
+
+/// Date for the `before` and `after` filters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SearchDate {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+}
+
+impl SearchDate {
+    pub fn new(year: u16, month: u8, day: u8) -> Self {
+        Self { year, month, day }
+    }
+}
+
+impl fmt::Display for SearchDate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02}/{:02}/{:04}", self.day, self.month, self.year)
+    }
+}
 
 #[derive(Default)]
 pub struct SslFilterBuilder {
@@ -10,19 +276,159 @@ impl SslFilterBuilder {
         self.filters
     }
 
-    pub fn cert_subject_cn(mut self, value: impl ToString) -> Self {
-        let values = self.filters.entry("cert.subject.cn".into()).or_default();
+    fn filter(mut self, name: &str, value: impl ToString) -> Self {
+        let values = self.filters.entry(format!("ssl.{name}")).or_default();
 
-        values.push(value.to_string());
+        values.push(quote_value(value.to_string()));
 
         self
     }
+
+    pub fn cert_subject_cn(self, value: impl ToString) -> Self {
+        self.filter("cert.subject.cn", value)
+    }
+
+    pub fn cert_issuer_cn(self, value: impl ToString) -> Self {
+        self.filter("cert.issuer.cn", value)
+    }
+
+    pub fn cert_expired(self, expired: bool) -> Self {
+        self.filter("cert.expired", expired)
+    }
+
+    /// Signature algorithm of the certificate, ex: `sha256WithRSAEncryption`.
+    pub fn cert_alg(self, value: impl ToString) -> Self {
+        self.filter("cert.alg", value)
+    }
+
+    pub fn cert_serial(self, value: impl ToString) -> Self {
+        self.filter("cert.serial", value)
+    }
+
+    pub fn cert_fingerprint(self, value: impl ToString) -> Self {
+        self.filter("cert.fingerprint", value)
+    }
+
+    pub fn cert_pubkey_bits(self, bits: u32) -> Self {
+        self.filter("cert.pubkey.bits", bits)
+    }
+
+    /// Public key type of the certificate, ex: `rsa` or `dsa`.
+    pub fn cert_pubkey_type(self, value: impl ToString) -> Self {
+        self.filter("cert.pubkey.type", value)
+    }
+
+    pub fn cert_extension(self, value: impl ToString) -> Self {
+        self.filter("cert.extension", value)
+    }
+
+    pub fn cipher_name(self, value: impl ToString) -> Self {
+        self.filter("cipher.name", value)
+    }
+
+    pub fn cipher_bits(self, bits: u32) -> Self {
+        self.filter("cipher.bits", bits)
+    }
+
+    pub fn cipher_version(self, value: impl ToString) -> Self {
+        self.filter("cipher.version", value)
+    }
+
+    /// Supported protocol version, ex: `tlsv1.3` or `-sslv3` for servers that don't support it.
+    pub fn version(self, value: impl ToString) -> Self {
+        self.filter("version", value)
+    }
+
+    /// Application layer protocol, ex: `h2` or `http/1.1`.
+    pub fn alpn(self, value: impl ToString) -> Self {
+        self.filter("alpn", value)
+    }
+
+    pub fn chain_count(self, count: u32) -> Self {
+        self.filter("chain_count", count)
+    }
+
+    pub fn jarm(self, value: impl ToString) -> Self {
+        self.filter("jarm", value)
+    }
+
+    pub fn ja3s(self, value: impl ToString) -> Self {
+        self.filter("ja3s", value)
+    }
 }
 
 #[derive(Default)]
+pub struct HttpFilterBuilder {
+    filters: HashMap<String, Vec<String>>,
+}
+
+impl HttpFilterBuilder {
+    pub fn build(self) -> HashMap<String, Vec<String>> {
+        self.filters
+    }
+
+    fn filter(mut self, name: &str, value: impl ToString) -> Self {
+        let values = self.filters.entry(format!("http.{name}")).or_default();
+
+        values.push(quote_value(value.to_string()));
+
+        self
+    }
+
+    pub fn title(self, value: impl ToString) -> Self {
+        self.filter("title", value)
+    }
+
+    pub fn html(self, value: impl ToString) -> Self {
+        self.filter("html", value)
+    }
+
+    pub fn html_hash(self, hash: i64) -> Self {
+        self.filter("html_hash", hash)
+    }
+
+    pub fn headers_hash(self, hash: i64) -> Self {
+        self.filter("headers_hash", hash)
+    }
+
+    pub fn status(self, status: u16) -> Self {
+        self.filter("status", status)
+    }
+
+    /// MurmurHash3 of the base64 encoded favicon.
+    pub fn favicon_hash(self, hash: i64) -> Self {
+        self.filter("favicon.hash", hash)
+    }
+
+    /// Web technology detected on the page, ex: `jQuery`.
+    pub fn component(self, value: impl ToString) -> Self {
+        self.filter("component", value)
+    }
+
+    pub fn component_category(self, value: impl ToString) -> Self {
+        self.filter("component_category", value)
+    }
+
+    pub fn robots_hash(self, hash: i64) -> Self {
+        self.filter("robots_hash", hash)
+    }
+
+    pub fn securitytxt(self, value: impl ToString) -> Self {
+        self.filter("securitytxt", value)
+    }
+
+    pub fn waf(self, value: impl ToString) -> Self {
+        self.filter("waf", value)
+    }
+}
+
+/// Builds search queries out of free text and filters. Filters are emitted sorted by name so
+/// that the same filters always produce the same query, negated filters come last.
+#[derive(Default, Clone)]
 pub struct SearchQueryBuilder {
     query: String,
-    filters: HashMap<String, Vec<String>>,
+    filters: BTreeMap<String, Vec<String>>,
+    negated: BTreeMap<String, Vec<String>>,
 }
 
 impl SearchQueryBuilder {
@@ -36,6 +442,10 @@ impl SearchQueryBuilder {
             query.push(format!("{}:{}", filter, values.join(",")));
         }
 
+        for (filter, values) in self.negated {
+            query.push(format!("-{}:{}", filter, values.join(",")));
+        }
+
         query.join(" ")
     }
 
@@ -45,34 +455,288 @@ impl SearchQueryBuilder {
         self
     }
 
-    pub fn port(mut self, value: impl ToString) -> Self {
-        let values = self.filters.entry("port".into()).or_default();
+    /// Adds a filter by name, for the filters that don't have a method of their own. Multiple
+    /// values for the same filter are matched as alternatives.
+    pub fn filter(mut self, name: impl ToString, value: impl ToString) -> Self {
+        let values = self.filters.entry(name.to_string()).or_default();
+
+        values.push(quote_value(value.to_string()));
+
+        self
+    }
+
+    /// Checks the filters added so far, see `QueryValidator`.
+    pub fn validate(self, validator: &QueryValidator) -> Result<Self, Error> {
+        validator.validate(&Query::parse(&self.clone().build())?)?;
+
+        Ok(self)
+    }
+
+    /// Excludes results matching the filters added in the closure, ex: `-port:22`. Any free text
+    /// query set in the closure is ignored.
+    pub fn not(mut self, closure: impl FnOnce(SearchQueryBuilder) -> SearchQueryBuilder) -> Self {
+        let excluded = closure(SearchQueryBuilder::default());
 
-        values.push(value.to_string());
+        merge_filters(&mut self.negated, excluded.filters);
+        // Negating a negation leaves a regular filter
+        merge_filters(&mut self.filters, excluded.negated);
 
         self
     }
 
-    pub fn product(mut self, filter: impl ToString) -> Self {
-        let values = self.filters.entry("product".into()).or_default();
+    pub fn port(self, value: impl ToString) -> Self {
+        self.filter("port", value)
+    }
+
+    pub fn product(self, filter: impl ToString) -> Self {
+        self.filter("product", filter)
+    }
+
+    pub fn version(self, value: impl ToString) -> Self {
+        self.filter("version", value)
+    }
+
+    /// Netblock, ex: `198.20.0.0/16`. A single address matches just that host.
+    pub fn net(self, net: impl Into<IpNet>) -> Self {
+        self.filter("net", net.into())
+    }
+
+    pub fn ip(self, ip: IpAddr) -> Self {
+        self.filter("ip", ip)
+    }
+
+    /// Autonomous system number, ex: `AS15169`.
+    pub fn asn(self, value: impl ToString) -> Self {
+        self.filter("asn", value)
+    }
+
+    pub fn org(self, value: impl ToString) -> Self {
+        self.filter("org", value)
+    }
+
+    pub fn isp(self, value: impl ToString) -> Self {
+        self.filter("isp", value)
+    }
+
+    pub fn hostname(self, value: impl ToString) -> Self {
+        self.filter("hostname", value)
+    }
+
+    pub fn domain(self, value: impl ToString) -> Self {
+        self.filter("domain", value)
+    }
 
-        values.push(filter.to_string());
+    /// Two-letter country code, ex: `NL`.
+    pub fn country(self, value: impl ToString) -> Self {
+        self.filter("country", value)
+    }
+
+    pub fn city(self, value: impl ToString) -> Self {
+        self.filter("city", value)
+    }
+
+    /// Region or state code, ex: `CA`.
+    pub fn region(self, value: impl ToString) -> Self {
+        self.filter("region", value)
+    }
+
+    pub fn postal(self, value: impl ToString) -> Self {
+        self.filter("postal", value)
+    }
+
+    /// Hosts within `radius` kilometers of the coordinates, or shodan's default radius when
+    /// omitted. Replaces any earlier `geo` filter as it takes a single location only.
+    pub fn geo(mut self, latitude: f64, longitude: f64, radius: Option<u32>) -> Self {
+        let value = match radius {
+            Some(radius) => format!("{latitude},{longitude},{radius}"),
+            None => format!("{latitude},{longitude}"),
+        };
+        self.filters.insert("geo".into(), vec![value]);
 
         self
     }
 
-    pub fn ssl(mut self, closure: fn(SslFilterBuilder) -> SslFilterBuilder) -> Self {
+    pub fn os(self, value: impl ToString) -> Self {
+        self.filter("os", value)
+    }
+
+    /// Type of device, ex: `webcam` or `router`.
+    pub fn device(self, value: impl ToString) -> Self {
+        self.filter("device", value)
+    }
+
+    pub fn cpe(self, value: impl ToString) -> Self {
+        self.filter("cpe", value)
+    }
+
+    /// Hash of the banner's data property.
+    pub fn hash(self, hash: i64) -> Self {
+        self.filter("hash", hash)
+    }
+
+    /// Type of network connection, ex: `DSL` or `VPN`.
+    pub fn link(self, value: impl ToString) -> Self {
+        self.filter("link", value)
+    }
+
+    /// CVE id, ex: `CVE-2014-0160`. Only available on academic and enterprise plans.
+    pub fn vuln(self, value: impl ToString) -> Self {
+        self.filter("vuln", value)
+    }
+
+    /// Only available on enterprise plans.
+    pub fn tag(self, value: impl ToString) -> Self {
+        self.filter("tag", value)
+    }
+
+    pub fn has_screenshot(self, has_screenshot: bool) -> Self {
+        self.filter("has_screenshot", has_screenshot)
+    }
+
+    pub fn has_ssl(self, has_ssl: bool) -> Self {
+        self.filter("has_ssl", has_ssl)
+    }
+
+    pub fn has_ipv6(self, has_ipv6: bool) -> Self {
+        self.filter("has_ipv6", has_ipv6)
+    }
+
+    pub fn has_vuln(self, has_vuln: bool) -> Self {
+        self.filter("has_vuln", has_vuln)
+    }
+
+    /// Label shodan's image classification gave the screenshot, ex: `ics` or `login`.
+    pub fn screenshot_label(self, value: impl ToString) -> Self {
+        self.filter("screenshot.label", value)
+    }
+
+    pub fn before(self, date: SearchDate) -> Self {
+        self.filter("before", date)
+    }
+
+    pub fn after(self, date: SearchDate) -> Self {
+        self.filter("after", date)
+    }
+
+    /// Cloud provider, ex: `Amazon` or `Google`.
+    pub fn cloud_provider(self, value: impl ToString) -> Self {
+        self.filter("cloud.provider", value)
+    }
+
+    pub fn cloud_region(self, value: impl ToString) -> Self {
+        self.filter("cloud.region", value)
+    }
+
+    pub fn cloud_service(self, value: impl ToString) -> Self {
+        self.filter("cloud.service", value)
+    }
+
+    /// HASSH fingerprint of the SSH server.
+    pub fn ssh_hassh(self, value: impl ToString) -> Self {
+        self.filter("ssh.hassh", value)
+    }
+
+    pub fn ssh_type(self, value: impl ToString) -> Self {
+        self.filter("ssh.type", value)
+    }
+
+    pub fn http(mut self, closure: impl FnOnce(HttpFilterBuilder) -> HttpFilterBuilder) -> Self {
+        let filters = closure(HttpFilterBuilder::default()).build();
+
+        merge_filters(&mut self.filters, filters);
+
+        self
+    }
+
+    pub fn ssl(mut self, closure: impl FnOnce(SslFilterBuilder) -> SslFilterBuilder) -> Self {
         let filters = closure(SslFilterBuilder::default()).build();
 
-        self.filters.extend(filters);
+        merge_filters(&mut self.filters, filters);
 
         self
     }
 }
 
+fn merge_filters(
+    filters: &mut BTreeMap<String, Vec<String>>,
+    other: impl IntoIterator<Item = (String, Vec<String>)>,
+) {
+    for (filter, values) in other {
+        filters.entry(filter).or_default().extend(values);
+    }
+}
+
+/// Wraps values that would otherwise break up the query in double quotes, ex: values with
+/// spaces or commas in them.
+pub(crate) fn quote_value(value: String) -> String {
+    let needs_quotes = value.is_empty()
+        || value
+            .chars()
+            .any(|c| c.is_whitespace() || matches!(c, ',' | '"' | ':' | '\\'));
+    if !needs_quotes {
+        return value;
+    }
+
+    let escaped = value.replace('\\', "\\\\").replace('"', "\\\"");
+    format!("\"{escaped}\"")
+}
+
 #[cfg(test)]
 mod tests {
     use crate::*;
+    use std::net::IpAddr;
+
+    #[test]
+    fn can_build_client_with_base_url() {
+        let client = ShodanClient::builder("KEY")
+            .base_url("http://127.0.0.1:8080/gateway/")
+            .user_agent("shodan-rs-tests")
+            .timeout(std::time::Duration::from_secs(5))
+            .build()
+            .unwrap();
+
+        let url = client
+            .build_request_url("/api-info", &Default::default())
+            .unwrap();
+
+        assert_eq!(url, "http://127.0.0.1:8080/gateway/api-info?key=KEY");
+    }
+
+    #[test]
+    fn rejects_invalid_base_url() {
+        let client = ShodanClient::builder("KEY").base_url("not a url").build();
+
+        assert!(matches!(client, Err(Error::UrlParse(_))));
+    }
+
+    #[test]
+    fn can_build_facets() {
+        let facets = FacetsBuilder::default()
+            .facet_with_size(FacetName::Country, 10)
+            .facet_with_size(FacetName::Port, 50)
+            .facet(FacetName::Org)
+            .facet("bitcoin.ip")
+            .build();
+
+        assert_eq!(facets, "country:10,port:50,org,bitcoin.ip");
+    }
+
+    #[test]
+    fn validates_facets() {
+        let available = vec![String::from("country"), String::from("org")];
+
+        assert!(FacetsBuilder::default()
+            .facet(FacetName::Country)
+            .validate(&available)
+            .is_ok());
+        assert!(matches!(
+            FacetsBuilder::default()
+                .facet(FacetName::Org)
+                .facet(FacetName::Vuln)
+                .validate(&available),
+            Err(Error::UnknownFacet(facet)) if facet == "vuln"
+        ));
+    }
 
     #[test]
     fn can_build_query() {
@@ -123,4 +787,93 @@ mod tests {
             "query was: {query}"
         );
     }
+
+    #[test]
+    fn can_build_with_typed_filters() {
+        let query = SearchQueryBuilder::default()
+            .net("198.20.0.0/16".parse::<IpNet>().unwrap())
+            .ip("8.8.8.8".parse().unwrap())
+            .asn("AS15169")
+            .country("NL")
+            .geo(52.37, 4.89, Some(50))
+            .has_screenshot(true)
+            .after(SearchDate::new(2023, 3, 1))
+            .cloud_provider("Amazon")
+            .http(|http| http.status(200).favicon_hash(-1137154858))
+            .ssl(|ssl| ssl.jarm("07d14d16d21d21d").cert_expired(false))
+            .build();
+
+        for filter in [
+            "net:198.20.0.0/16",
+            "ip:8.8.8.8",
+            "asn:AS15169",
+            "country:NL",
+            "geo:52.37,4.89,50",
+            "has_screenshot:true",
+            "after:01/03/2023",
+            "cloud.provider:Amazon",
+            "http.status:200",
+            "http.favicon.hash:-1137154858",
+            "ssl.jarm:07d14d16d21d21d",
+            "ssl.cert.expired:false",
+        ] {
+            assert!(
+                query.contains(filter),
+                "{filter} missing from query: {query}"
+            );
+        }
+    }
+
+    #[test]
+    fn can_build_with_ipv6_addresses() {
+        let query = SearchQueryBuilder::default()
+            .ip("2001:4860:4860::8888".parse::<IpAddr>().unwrap())
+            .net("2001:4860::/32".parse::<IpNet>().unwrap())
+            .build();
+
+        assert_eq!(query, r#"ip:"2001:4860:4860::8888" net:"2001:4860::/32""#);
+    }
+
+    #[test]
+    fn quotes_values() {
+        let query = SearchQueryBuilder::default()
+            .product("Apache httpd")
+            .filter("http.title", "Hello, \"World\"")
+            .port(80)
+            .build();
+
+        assert_eq!(
+            query,
+            r#"http.title:"Hello, \"World\"" port:80 product:"Apache httpd""#
+        );
+    }
+
+    #[test]
+    fn can_build_with_negations() {
+        let query = SearchQueryBuilder::default()
+            .query("nginx")
+            .not(|q| q.port(22).port(23))
+            .country("NL")
+            .not(|q| q.not(|q| q.org("Amazon")))
+            .build();
+
+        assert_eq!(query, "nginx country:NL org:Amazon -port:22,23");
+    }
+
+    #[test]
+    fn builds_filters_in_stable_order() {
+        let first = SearchQueryBuilder::default()
+            .port(443)
+            .ssl(|ssl| ssl.alpn("h2"))
+            .country("NL")
+            .build();
+        let second = SearchQueryBuilder::default()
+            .country("NL")
+            .ssl(|ssl| ssl.alpn("h2"))
+            .port(443)
+            .build();
+
+        assert_eq!(first, second);
+        assert_eq!(first, "country:NL port:443 ssl.alpn:h2");
+    }
 }
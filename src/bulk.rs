@@ -0,0 +1,660 @@
+use crate::*;
+use async_compression::tokio::bufread::GzipDecoder;
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::stream::{self, BoxStream, StreamExt, TryStreamExt};
+use reqwest::header::RANGE;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use sha1::{Digest, Sha1};
+use std::path::Path;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio_util::io::StreamReader;
+
+#[async_trait]
+pub trait Bulk {
+    /// Lists the datasets available for download on the current plan.
+    async fn get_datasets(&self) -> Result<Vec<Dataset>, Error>;
+
+    async fn get_dataset_files(&self, dataset: String) -> Result<Vec<DatasetFile>, Error>;
+
+    /// Prepares the download of a dataset file. Nothing is requested until the download is
+    /// consumed with `bytes`, `banners` or `to_file`.
+    fn download_dataset_file(&self, file: &DatasetFile) -> DatasetDownload;
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Dataset {
+    pub name: String,
+    pub scope: String,
+    pub description: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct DatasetFile {
+    pub name: String,
+    /// Pre-signed download link, it doesn't need the API key and expires after a while.
+    pub url: String,
+    pub sha1: String,
+    /// Milliseconds since the unix epoch.
+    pub timestamp: u64,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DownloadProgress {
+    pub downloaded: u64,
+    pub total: u64,
+}
+
+type ProgressCallback = Arc<dyn Fn(DownloadProgress) + Send + Sync>;
+
+/// Download of a single dataset file. Dropped and stalled connections are resumed with a
+/// `Range` request and, when the whole file passes through, the contents are checked against
+/// the listed SHA1.
+pub struct DatasetDownload {
+    http_client: reqwest::Client,
+    file: DatasetFile,
+    offset: u64,
+    hasher: Option<Sha1>,
+    max_resumes: u32,
+    resume_backoff: Duration,
+    idle_timeout: Duration,
+    progress: Option<ProgressCallback>,
+}
+
+impl DatasetDownload {
+    /// Starts the download at `offset` bytes into the file. The checksum can't be verified when
+    /// skipping part of the file, use `to_file` to resume a partial download with verification.
+    pub fn resume_from(mut self, offset: u64) -> Self {
+        self.offset = offset;
+        self.hasher = None;
+
+        self
+    }
+
+    /// Amount of times a dropped connection is picked back up before giving up. Defaults to 3.
+    pub fn max_resumes(mut self, max_resumes: u32) -> Self {
+        self.max_resumes = max_resumes;
+
+        self
+    }
+
+    /// Time to wait before the first resume, doubled for every resume after it. Defaults to 1
+    /// second.
+    pub fn resume_backoff(mut self, backoff: Duration) -> Self {
+        self.resume_backoff = backoff;
+
+        self
+    }
+
+    /// How long the connection may go without receiving anything before it's considered
+    /// stalled and resumed. Defaults to 60 seconds.
+    pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = timeout;
+
+        self
+    }
+
+    /// Gets called after every received chunk.
+    pub fn on_progress(
+        mut self,
+        progress: impl Fn(DownloadProgress) + Send + Sync + 'static,
+    ) -> Self {
+        self.progress = Some(Arc::new(progress));
+
+        self
+    }
+
+    /// Streams the file as it is stored, gzip compressed. A checksum mismatch is reported as
+    /// the last item of the stream.
+    pub fn bytes(self) -> BoxStream<'static, Result<Bytes, Error>> {
+        let state = DownloadState {
+            response: None,
+            downloaded: self.offset,
+            skip: 0,
+            resumes_left: self.max_resumes,
+            done: false,
+            download: self,
+        };
+
+        stream::unfold(state, |mut state| async move {
+            state.next_chunk().await.map(|chunk| (chunk, state))
+        })
+        .boxed()
+    }
+
+    /// Decompresses the file and decodes every line into a banner, ex: `SearchResultMatch` or
+    /// `serde_json::Value` for the raw records.
+    pub fn banners<T: DeserializeOwned + Send + 'static>(
+        self,
+    ) -> BoxStream<'static, Result<T, Error>> {
+        let reader = StreamReader::new(self.bytes().map_err(std::io::Error::other));
+        let mut decoder = GzipDecoder::new(reader);
+        decoder.multiple_members(true);
+
+        let lines = BufReader::new(decoder).lines();
+        stream::try_unfold(lines, |mut lines| async move {
+            loop {
+                match lines.next_line().await.map_err(unwrap_io_error)? {
+                    Some(line) if line.trim().is_empty() => continue,
+                    Some(line) => return Ok(Some((serde_json::from_str(&line)?, lines))),
+                    None => return Ok(None),
+                }
+            }
+        })
+        .boxed()
+    }
+
+    /// Downloads the file to `path`. An existing partial download at `path` is picked up where
+    /// it left off and included in the checksum verification, a complete one is only verified.
+    /// The file is truncated when its contents don't match the checksum.
+    pub async fn to_file(mut self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let mut output = tokio::fs::OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(path.as_ref())
+            .await?;
+
+        let existing = output.metadata().await?.len();
+        if existing > self.file.size {
+            output.set_len(0).await?;
+        } else if existing > 0 {
+            let mut hasher = Sha1::new();
+            let mut buffer = vec![0; 64 * 1024];
+            loop {
+                let read = output.read(&mut buffer).await?;
+                if read == 0 {
+                    break;
+                }
+                hasher.update(&buffer[..read]);
+            }
+
+            // There's nothing left to request, S3 answers a range past the end with a 416
+            if existing == self.file.size {
+                if verify_checksum(&self.file, hasher).is_ok() {
+                    return Ok(());
+                }
+                output.set_len(0).await?;
+            } else {
+                self.offset = existing;
+                self.hasher = Some(hasher);
+            }
+        }
+
+        let mut chunks = self.bytes();
+        while let Some(chunk) = chunks.next().await {
+            match chunk {
+                Ok(chunk) => output.write_all(&chunk).await?,
+                Err(e @ Error::ChecksumMismatch { .. }) => {
+                    output.set_len(0).await?;
+                    return Err(e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(output.flush().await?)
+    }
+}
+
+struct DownloadState {
+    download: DatasetDownload,
+    response: Option<reqwest::Response>,
+    /// Position in the file, including any offset the download started at.
+    downloaded: u64,
+    /// Bytes to drop from the current response, for servers that ignore the `Range` header.
+    skip: u64,
+    resumes_left: u32,
+    done: bool,
+}
+
+impl DownloadState {
+    async fn next_chunk(&mut self) -> Option<Result<Bytes, Error>> {
+        loop {
+            if self.done {
+                return None;
+            }
+
+            let response = match self.response.as_mut() {
+                Some(response) => response,
+                None => match self.request().await {
+                    Ok(response) => self.response.insert(response),
+                    Err(e) => return self.fail(e),
+                },
+            };
+
+            let idle_timeout = self.download.idle_timeout;
+            let Ok(chunk) = tokio::time::timeout(idle_timeout, response.chunk()).await else {
+                self.response = None;
+                if self.resume().await.is_err() {
+                    return self.fail(Error::DownloadStalled(idle_timeout));
+                }
+                continue;
+            };
+
+            match chunk {
+                Ok(Some(mut chunk)) => {
+                    if self.skip > 0 {
+                        let skipped = self.skip.min(chunk.len() as u64);
+                        self.skip -= skipped;
+                        chunk = chunk.slice(skipped as usize..);
+                        if chunk.is_empty() {
+                            continue;
+                        }
+                    }
+
+                    if let Some(hasher) = self.download.hasher.as_mut() {
+                        hasher.update(&chunk);
+                    }
+                    self.downloaded += chunk.len() as u64;
+                    if let Some(progress) = &self.download.progress {
+                        progress(DownloadProgress {
+                            downloaded: self.downloaded,
+                            total: self.download.file.size,
+                        });
+                    }
+
+                    return Some(Ok(chunk));
+                }
+                Ok(None) if self.downloaded < self.download.file.size => {
+                    self.response = None;
+                    if let Err(e) = self.resume().await {
+                        return self.fail(e);
+                    }
+                }
+                Ok(None) => {
+                    self.done = true;
+                    return self.verify().err().map(Err);
+                }
+                Err(e) if e.is_body() || e.is_timeout() => {
+                    self.response = None;
+                    if self.resume().await.is_err() {
+                        return self.fail(e.into());
+                    }
+                }
+                Err(e) => return self.fail(e.into()),
+            }
+        }
+    }
+
+    async fn request(&mut self) -> Result<reqwest::Response, Error> {
+        let mut request = self
+            .download
+            .http_client
+            .get(self.download.file.url.as_str());
+        if self.downloaded > 0 {
+            request = request.header(RANGE, format!("bytes={}-", self.downloaded));
+        }
+
+        let response = request.send().await?.error_for_status()?;
+        self.skip = match response.status() {
+            StatusCode::PARTIAL_CONTENT => 0,
+            _ => self.downloaded,
+        };
+
+        Ok(response)
+    }
+
+    /// Uses up one of the resumes and waits out the backoff before the next request.
+    async fn resume(&mut self) -> Result<(), Error> {
+        if self.resumes_left == 0 {
+            return Err(Error::Io(std::io::ErrorKind::UnexpectedEof.into()));
+        }
+
+        let resumed = self.download.max_resumes - self.resumes_left;
+        self.resumes_left -= 1;
+        let backoff = self
+            .download
+            .resume_backoff
+            .saturating_mul(2u32.saturating_pow(resumed));
+        tokio::time::sleep(backoff).await;
+
+        Ok(())
+    }
+
+    fn verify(&mut self) -> Result<(), Error> {
+        match self.download.hasher.take() {
+            Some(hasher) => verify_checksum(&self.download.file, hasher),
+            None => Ok(()),
+        }
+    }
+
+    fn fail(&mut self, error: Error) -> Option<Result<Bytes, Error>> {
+        self.done = true;
+        self.response = None;
+
+        Some(Err(error))
+    }
+}
+
+fn verify_checksum(file: &DatasetFile, hasher: Sha1) -> Result<(), Error> {
+    let actual = hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect::<String>();
+
+    if !actual.eq_ignore_ascii_case(&file.sha1) {
+        return Err(Error::ChecksumMismatch {
+            file: file.name.clone(),
+            expected: file.sha1.clone(),
+            actual,
+        });
+    }
+
+    Ok(())
+}
+
+/// Gets our own error back out of the IO error it was wrapped in to pass through the decoder.
+fn unwrap_io_error(error: std::io::Error) -> Error {
+    if !error.get_ref().is_some_and(|inner| inner.is::<Error>()) {
+        return Error::Io(error);
+    }
+
+    let inner = error.into_inner().expect("Inner error was checked above");
+    *inner
+        .downcast::<Error>()
+        .expect("Inner error type was checked above")
+}
+
+#[async_trait]
+impl Bulk for ShodanClient {
+    async fn get_datasets(&self) -> Result<Vec<Dataset>, Error> {
+        self.fetch(self.build_request_url("/shodan/data", &Default::default())?)
+            .await
+    }
+
+    async fn get_dataset_files(&self, dataset: String) -> Result<Vec<DatasetFile>, Error> {
+        self.fetch(self.build_request_url(
+            format!("/shodan/data/{dataset}").as_str(),
+            &Default::default(),
+        )?)
+        .await
+    }
+
+    fn download_dataset_file(&self, file: &DatasetFile) -> DatasetDownload {
+        DatasetDownload {
+            http_client: self.http_client.clone(),
+            file: file.clone(),
+            offset: 0,
+            hasher: Some(Sha1::new()),
+            max_resumes: 3,
+            resume_backoff: Duration::from_secs(1),
+            idle_timeout: Duration::from_secs(60),
+            progress: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::MockServer;
+    use crate::tests::{banner_json, test_client};
+    use crate::*;
+    use async_compression::tokio::write::GzipEncoder;
+    use futures::StreamExt;
+    use reqwest::header::RANGE;
+    use sha1::{Digest, Sha1};
+    use std::sync::Mutex;
+    use std::time::Duration;
+    use tokio::io::AsyncWriteExt;
+
+    /// Both banners of the search fixture, one per line like in the datasets.
+    fn banners() -> String {
+        [0, 1]
+            .map(|index| format!("{}\n", banner_json(index, serde_json::json!({}))))
+            .concat()
+    }
+
+    async fn gzip(contents: &[u8]) -> Vec<u8> {
+        let mut encoder = GzipEncoder::new(vec![]);
+        encoder.write_all(contents).await.unwrap();
+        encoder.shutdown().await.unwrap();
+
+        encoder.into_inner()
+    }
+
+    fn dataset_file(server: &MockServer, contents: &[u8]) -> DatasetFile {
+        server.serve_file("/raw-daily/2023-03-01.json.gz", contents);
+
+        DatasetFile {
+            name: String::from("2023-03-01.json.gz"),
+            url: format!("{}/raw-daily/2023-03-01.json.gz", server.url()),
+            sha1: Sha1::digest(contents)
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect(),
+            timestamp: 1677628800000,
+            size: contents.len() as u64,
+        }
+    }
+
+    #[tokio::test]
+    async fn can_get_datasets() {
+        let client = test_client().await;
+        client.get_datasets().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn can_get_dataset_files() {
+        let client = test_client().await;
+        client
+            .get_dataset_files(String::from("raw-daily"))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn can_download_banners() {
+        let server = MockServer::with_fixtures().await;
+        let file = dataset_file(&server, &gzip(banners().as_bytes()).await);
+
+        let progress = Arc::new(Mutex::new(vec![]));
+        let reported = progress.clone();
+        let banners = server
+            .client()
+            .download_dataset_file(&file)
+            .on_progress(move |progress| reported.lock().unwrap().push(progress))
+            .banners::<serde_json::Value>()
+            .collect::<Vec<_>>()
+            .await;
+
+        let ips = banners
+            .into_iter()
+            .map(|banner| banner.unwrap()["ip_str"].as_str().unwrap().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(ips, vec!["142.250.64.78", "8.8.8.8"]);
+        assert_eq!(
+            progress.lock().unwrap().last(),
+            Some(&DownloadProgress {
+                downloaded: file.size,
+                total: file.size
+            })
+        );
+        assert!(server.received_requests()[0].query_param("key").is_none());
+    }
+
+    #[tokio::test]
+    async fn resumes_interrupted_download() {
+        let server = MockServer::with_fixtures().await;
+        let contents = banners().repeat(100).into_bytes();
+        let file = dataset_file(&server, &contents);
+        server.interrupt_file("/raw-daily/2023-03-01.json.gz", 1000);
+
+        let chunks = server
+            .client()
+            .download_dataset_file(&file)
+            .resume_backoff(Duration::from_millis(10))
+            .bytes()
+            .collect::<Vec<_>>()
+            .await;
+
+        let downloaded = chunks
+            .into_iter()
+            .flat_map(|chunk| chunk.unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(downloaded, contents);
+
+        let requests = server.received_requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[1].headers[RANGE], "bytes=1000-");
+    }
+
+    #[tokio::test]
+    async fn resumes_stalled_download() {
+        let server = MockServer::with_fixtures().await;
+        let contents = banners().repeat(100).into_bytes();
+        let file = dataset_file(&server, &contents);
+        server.stall_file("/raw-daily/2023-03-01.json.gz", 1000);
+
+        let started = std::time::Instant::now();
+        let chunks = server
+            .client()
+            .download_dataset_file(&file)
+            .idle_timeout(Duration::from_millis(100))
+            .resume_backoff(Duration::from_millis(100))
+            .bytes()
+            .collect::<Vec<_>>()
+            .await;
+
+        let downloaded = chunks
+            .into_iter()
+            .flat_map(|chunk| chunk.unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(downloaded, contents);
+        assert!(started.elapsed() >= Duration::from_millis(200));
+
+        let requests = server.received_requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[1].headers[RANGE], "bytes=1000-");
+    }
+
+    #[tokio::test]
+    async fn gives_up_on_stalled_download() {
+        let server = MockServer::with_fixtures().await;
+        let file = dataset_file(&server, banners().as_bytes());
+        for _ in 0..2 {
+            server.stall_file("/raw-daily/2023-03-01.json.gz", 10);
+        }
+
+        let mut chunks = server
+            .client()
+            .download_dataset_file(&file)
+            .max_resumes(1)
+            .idle_timeout(Duration::from_millis(50))
+            .resume_backoff(Duration::ZERO)
+            .bytes();
+        let mut last = None;
+        while let Some(chunk) = chunks.next().await {
+            last = Some(chunk);
+        }
+
+        assert!(matches!(last, Some(Err(Error::DownloadStalled(_)))));
+    }
+
+    #[tokio::test]
+    async fn rejects_checksum_mismatch() {
+        let server = MockServer::with_fixtures().await;
+        let mut file = dataset_file(&server, banners().as_bytes());
+        file.sha1 = String::from("0000000000000000000000000000000000000000");
+
+        let mut chunks = server.client().download_dataset_file(&file).bytes();
+        let mut last = None;
+        while let Some(chunk) = chunks.next().await {
+            last = Some(chunk);
+        }
+
+        assert!(matches!(last, Some(Err(Error::ChecksumMismatch { .. }))));
+    }
+
+    #[tokio::test]
+    async fn resumes_partial_file() {
+        let server = MockServer::with_fixtures().await;
+        let contents = banners().repeat(10).into_bytes();
+        let file = dataset_file(&server, &contents);
+
+        let path = std::env::temp_dir().join(format!(
+            "shodan-rs-{}-resumes_partial_file.json",
+            std::process::id()
+        ));
+        std::fs::write(&path, &contents[..100]).unwrap();
+
+        server
+            .client()
+            .download_dataset_file(&file)
+            .to_file(&path)
+            .await
+            .unwrap();
+
+        assert_eq!(std::fs::read(&path).unwrap(), contents);
+        assert_eq!(server.received_requests()[0].headers[RANGE], "bytes=100-");
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn skips_complete_file() {
+        let server = MockServer::with_fixtures().await;
+        let contents = banners().repeat(10).into_bytes();
+        let file = dataset_file(&server, &contents);
+
+        let path = std::env::temp_dir().join(format!(
+            "shodan-rs-{}-skips_complete_file.json",
+            std::process::id()
+        ));
+        std::fs::remove_file(&path).ok();
+
+        for _ in 0..2 {
+            server
+                .client()
+                .download_dataset_file(&file)
+                .to_file(&path)
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(std::fs::read(&path).unwrap(), contents);
+        assert_eq!(server.received_requests().len(), 1);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn truncates_corrupt_file() {
+        let server = MockServer::with_fixtures().await;
+        let contents = banners().repeat(10).into_bytes();
+        let mut file = dataset_file(&server, &contents);
+
+        let path = std::env::temp_dir().join(format!(
+            "shodan-rs-{}-truncates_corrupt_file.json",
+            std::process::id()
+        ));
+
+        // A complete file that doesn't match the checksum is downloaded again from scratch
+        std::fs::write(&path, vec![b'x'; contents.len()]).unwrap();
+        server
+            .client()
+            .download_dataset_file(&file)
+            .to_file(&path)
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), contents);
+        assert!(server.received_requests()[0].headers.get(RANGE).is_none());
+
+        std::fs::write(&path, &contents[..100]).unwrap();
+        file.sha1 = String::from("0000000000000000000000000000000000000000");
+        let error = server
+            .client()
+            .download_dataset_file(&file)
+            .to_file(&path)
+            .await
+            .unwrap_err();
+        assert!(matches!(error, Error::ChecksumMismatch { .. }));
+        assert!(std::fs::read(&path).unwrap().is_empty());
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -2,8 +2,43 @@ use crate::*;
 use async_trait::async_trait;
 use serde::Deserialize;
 
+#[non_exhaustive]
+#[derive(Debug, Clone, Default)]
+pub struct DirectoryQueryRequest {
+    pub page: Option<u32>,
+    pub sort: Option<String>,
+    pub order: Option<String>,
+}
+
+impl DirectoryQueryRequest {
+    pub fn page(mut self, page: u32) -> Self {
+        self.page = Some(page);
+
+        self
+    }
+
+    /// Property to sort the saved queries by, either `votes` or `timestamp`.
+    pub fn sort(mut self, sort: impl Into<String>) -> Self {
+        self.sort = Some(sort.into());
+
+        self
+    }
+
+    /// Either `asc` or `desc`.
+    pub fn order(mut self, order: impl Into<String>) -> Self {
+        self.order = Some(order.into());
+
+        self
+    }
+}
+
 #[async_trait]
 pub trait Directory {
+    async fn directory_query_with(
+        &self,
+        request: DirectoryQueryRequest,
+    ) -> Result<ShodanClientResponse<DirectoryQueryResponse>, Error>;
+
     async fn directory_query(
         &self,
         page: Option<u32>,
@@ -39,6 +74,14 @@ pub struct DirectoryQueryResponseMatch {
     pub query: String,
 }
 
+#[cfg(feature = "chrono")]
+impl DirectoryQueryResponseMatch {
+    /// When the query was saved to the directory.
+    pub fn timestamp_utc(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        crate::timestamp::parse(&self.timestamp)
+    }
+}
+
 #[derive(Deserialize, Debug)]
 pub struct DirectoryQueryTagsResponse {
     pub matches: Vec<DirectoryQueryTagsResponseMatch>,
@@ -53,18 +96,27 @@ pub struct DirectoryQueryTagsResponseMatch {
 
 #[async_trait]
 impl Directory for ShodanClient {
+    async fn directory_query_with(
+        &self,
+        request: DirectoryQueryRequest,
+    ) -> Result<ShodanClientResponse<DirectoryQueryResponse>, Error> {
+        let mut parameters = ParameterBag::default();
+        parameters.set_optional("page", request.page);
+        parameters.set_optional("sort", request.sort);
+        parameters.set_optional("order", request.order);
+
+        self.fetch(self.build_request_url("/shodan/query", &parameters)?)
+            .await
+    }
+
     async fn directory_query(
         &self,
         page: Option<u32>,
         sort: Option<String>,
         order: Option<String>,
     ) -> Result<ShodanClientResponse<DirectoryQueryResponse>, Error> {
-        let mut parameters = ParameterBag::default();
-        parameters.set_optional("page", page);
-        parameters.set_optional("sort", sort);
-        parameters.set_optional("order", order);
-
-        Self::fetch(self.build_request_url("/shodan/query", &parameters)?).await
+        self.directory_query_with(DirectoryQueryRequest { page, sort, order })
+            .await
     }
 
     async fn directory_query_search(
@@ -76,7 +128,8 @@ impl Directory for ShodanClient {
         parameters.set("query", query);
         parameters.set_optional("page", page);
 
-        Self::fetch(self.build_request_url("/shodan/query/search", &parameters)?).await
+        self.fetch(self.build_request_url("/shodan/query/search", &parameters)?)
+            .await
     }
 
     async fn directory_query_tags(
@@ -86,24 +139,25 @@ impl Directory for ShodanClient {
         let mut parameters = ParameterBag::default();
         parameters.set_optional("size", size);
 
-        Self::fetch(self.build_request_url("/shodan/query/tags", &parameters)?).await
+        self.fetch(self.build_request_url("/shodan/query/tags", &parameters)?)
+            .await
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::tests::get_test_api_key;
+    use crate::tests::test_client;
     use crate::*;
 
     #[tokio::test]
     async fn can_get_directory_query() {
-        let client = ShodanClient::new(get_test_api_key());
+        let client = test_client().await;
         client.directory_query(None, None, None).await.unwrap();
     }
 
     #[tokio::test]
     async fn can_get_directory_query_search() {
-        let client = ShodanClient::new(get_test_api_key());
+        let client = test_client().await;
         client
             .directory_query_search(String::from("webcam"), None)
             .await
@@ -112,7 +166,7 @@ mod tests {
 
     #[tokio::test]
     async fn can_get_directory_query_tags() {
-        let client = ShodanClient::new(get_test_api_key());
+        let client = test_client().await;
         client.directory_query_tags(None).await.unwrap();
     }
 }
@@ -1,6 +1,7 @@
 use crate::*;
 use async_trait::async_trait;
 use serde::Deserialize;
+use std::net::IpAddr;
 
 #[derive(Deserialize, Debug)]
 pub struct DnsDomainResponse {
@@ -20,8 +21,56 @@ pub struct DnsDomainDataItem {
     pub last_seen: String,
 }
 
+#[cfg(feature = "chrono")]
+impl DnsDomainDataItem {
+    /// When shodan last saw the record.
+    pub fn last_seen_utc(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        crate::timestamp::parse(&self.last_seen)
+    }
+}
+
+#[non_exhaustive]
+#[derive(Debug, Clone, Default)]
+pub struct DnsDomainRequest {
+    pub domain: String,
+    pub history: Option<bool>,
+    pub dns_type: Option<String>,
+    pub page: Option<u32>,
+}
+
+impl DnsDomainRequest {
+    pub fn new(domain: impl Into<String>) -> Self {
+        Self {
+            domain: domain.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Includes DNS records that are no longer active.
+    pub fn history(mut self, history: bool) -> Self {
+        self.history = Some(history);
+
+        self
+    }
+
+    /// Only returns records of this type, ex: `A`, `AAAA`, `CNAME`, `NS`, `SOA`, `MX` or `TXT`.
+    pub fn dns_type(mut self, dns_type: impl Into<String>) -> Self {
+        self.dns_type = Some(dns_type.into());
+
+        self
+    }
+
+    pub fn page(mut self, page: u32) -> Self {
+        self.page = Some(page);
+
+        self
+    }
+}
+
 #[async_trait]
 pub trait Dns {
+    async fn dns_domain_with(&self, request: DnsDomainRequest) -> Result<DnsDomainResponse, Error>;
+
     async fn dns_domain(
         &self,
         domain: String,
@@ -33,13 +82,26 @@ pub trait Dns {
     async fn dns_resolve(
         &self,
         hostnames: Vec<String>,
-    ) -> Result<HashMap<String, Option<String>>, Error>;
+    ) -> Result<HashMap<String, Option<IpAddr>>, Error>;
 
-    async fn dns_reverse(&self, ips: Vec<String>) -> Result<HashMap<String, Vec<String>>, Error>;
+    async fn dns_reverse(&self, ips: Vec<IpAddr>) -> Result<HashMap<IpAddr, Vec<String>>, Error>;
 }
 
 #[async_trait]
 impl Dns for ShodanClient {
+    async fn dns_domain_with(&self, request: DnsDomainRequest) -> Result<DnsDomainResponse, Error> {
+        let mut parameters = ParameterBag::default();
+        parameters.set_optional("history", request.history);
+        parameters.set_optional("type", request.dns_type);
+        parameters.set_optional("page", request.page);
+
+        self.fetch_metered(self.build_request_url(
+            format!("/dns/domain/{}", request.domain).as_str(),
+            &parameters,
+        )?)
+        .await
+    }
+
     async fn dns_domain(
         &self,
         domain: String,
@@ -47,41 +109,46 @@ impl Dns for ShodanClient {
         dns_type: Option<String>,
         page: Option<u32>,
     ) -> Result<DnsDomainResponse, Error> {
-        let mut parameters = ParameterBag::default();
-        parameters.set_optional("history", history);
-        parameters.set_optional("dns_type", dns_type);
-        parameters.set_optional("page", page);
-
-        Self::fetch(self.build_request_url(format!("/dns/domain/{domain}").as_str(), &parameters)?)
-            .await
+        self.dns_domain_with(DnsDomainRequest {
+            history,
+            dns_type,
+            page,
+            ..DnsDomainRequest::new(domain)
+        })
+        .await
     }
 
     async fn dns_resolve(
         &self,
         hostnames: Vec<String>,
-    ) -> Result<HashMap<String, Option<String>>, Error> {
+    ) -> Result<HashMap<String, Option<IpAddr>>, Error> {
         let mut parameters = ParameterBag::default();
         parameters.set("hostnames", hostnames.join(","));
 
-        Self::fetch(self.build_request_url("/dns/resolve", &parameters)?).await
+        self.fetch(self.build_request_url("/dns/resolve", &parameters)?)
+            .await
     }
 
-    async fn dns_reverse(&self, ips: Vec<String>) -> Result<HashMap<String, Vec<String>>, Error> {
+    async fn dns_reverse(&self, ips: Vec<IpAddr>) -> Result<HashMap<IpAddr, Vec<String>>, Error> {
+        let ips = ips.iter().map(IpAddr::to_string).collect::<Vec<_>>();
+
         let mut parameters = ParameterBag::default();
         parameters.set("ips", ips.join(","));
 
-        Self::fetch(self.build_request_url("/dns/reverse", &parameters)?).await
+        self.fetch(self.build_request_url("/dns/reverse", &parameters)?)
+            .await
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::tests::get_test_api_key;
+    use crate::testing::MockServer;
+    use crate::tests::test_client;
     use crate::*;
 
     #[tokio::test]
     async fn can_get_dns_domain() {
-        let client = ShodanClient::new(get_test_api_key());
+        let client = test_client().await;
         client
             .dns_domain(String::from("google.com"), None, None, None)
             .await
@@ -90,7 +157,7 @@ mod tests {
 
     #[tokio::test]
     async fn can_get_dns_resolve() {
-        let client = ShodanClient::new(get_test_api_key());
+        let client = test_client().await;
         client
             .dns_resolve(vec![
                 String::from("google.com"),
@@ -102,10 +169,44 @@ mod tests {
 
     #[tokio::test]
     async fn can_get_dns_reverse() {
-        let client = ShodanClient::new(get_test_api_key());
+        let client = test_client().await;
         client
-            .dns_reverse(vec![String::from("8.8.8.8"), String::from("1.1.1.1")])
+            .dns_reverse(vec![
+                "8.8.8.8".parse().unwrap(),
+                "2606:4700:4700::1111".parse().unwrap(),
+            ])
             .await
             .unwrap();
     }
+
+    #[tokio::test]
+    async fn reverses_ipv6_addresses() {
+        let server = MockServer::with_fixtures().await;
+        let ip = "2606:4700:4700::1111".parse().unwrap();
+        let hostnames = server.client().dns_reverse(vec![ip]).await.unwrap();
+
+        assert_eq!(hostnames[&ip], vec!["one.one.one.one"]);
+        assert_eq!(
+            server.received_requests()[0].query_param("ips"),
+            Some("2606:4700:4700::1111")
+        );
+    }
+
+    #[tokio::test]
+    async fn can_get_dns_domain_with_request() {
+        let server = MockServer::with_fixtures().await;
+        server
+            .client()
+            .dns_domain_with(
+                DnsDomainRequest::new("google.com")
+                    .dns_type("MX")
+                    .history(true),
+            )
+            .await
+            .unwrap();
+
+        let request = &server.received_requests()[0];
+        assert_eq!(request.query_param("type"), Some("MX"));
+        assert_eq!(request.query_param("history"), Some("true"));
+    }
 }
@@ -1,3 +1,8 @@
+use crate::{ErrorResponse, QueryIssue, ScanStatus};
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::StatusCode;
+use std::fmt;
+use std::time::{Duration, SystemTime};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -5,9 +10,264 @@ pub enum Error {
     #[error("Couldn't parse URL: {0}")]
     UrlParse(#[from] url::ParseError),
 
+    #[error("Shodan API rejected the API key: {0}")]
+    Unauthorized(ApiError),
+
+    #[error("Insufficient credits: {0}")]
+    InsufficientCredits(ApiError),
+
+    #[error("Not available on the current plan: {0}")]
+    PlanRestriction(ApiError),
+
+    #[error("Rate limited by the Shodan API: {error}")]
+    RateLimited {
+        error: ApiError,
+        retry_after: Option<Duration>,
+    },
+
+    #[error("Not found: {0}")]
+    NotFound(ApiError),
+
+    #[error("Invalid query: {0}")]
+    InvalidQuery(ApiError),
+
+    #[error("Shodan server error: {0}")]
+    Server(ApiError),
+
     #[error("Shodan API error: {0}")]
-    Shodan(String),
+    Shodan(ApiError),
+
+    #[error("Couldn't deserialize response: {0}")]
+    Deserialize(#[from] serde_json::Error),
 
     #[error("Caught reqwest error: {0}")]
     Reqwest(#[from] reqwest::Error),
+
+    #[error("Invalid query syntax: {}", .0.join(", "))]
+    QuerySyntax(Vec<String>),
+
+    #[error("Query failed validation: {}", .0.iter().map(ToString::to_string).collect::<Vec<_>>().join(", "))]
+    QueryValidation(Vec<QueryIssue>),
+
+    #[error("Unknown facet: {0}")]
+    UnknownFacet(String),
+
+    #[error("Unknown scan protocol: {0}")]
+    UnknownProtocol(String),
+
+    #[error("Scan {id} did not finish in time, last status was {status:?}")]
+    ScanTimeout { id: String, status: ScanStatus },
+
+    #[error("No recorded interaction for {method} {url}")]
+    UnmatchedRequest { method: String, url: String },
+
+    #[error("Invalid cassette: {0}")]
+    Cassette(String),
+
+    #[error("Checksum mismatch for {file}, expected SHA1 {expected} but got {actual}")]
+    ChecksumMismatch {
+        file: String,
+        expected: String,
+        actual: String,
+    },
+
+    #[error("Stream was closed by the server")]
+    StreamClosed,
+
+    #[error("Nothing was received on the stream for {0:?}")]
+    StreamTimeout(Duration),
+
+    #[error("Nothing was received on the download for {0:?}")]
+    DownloadStalled(Duration),
+
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+impl Error {
+    /// Maps an error response from the API onto the matching variant. Shodan isn't entirely
+    /// consistent with its status codes so the message is taken into account as well.
+    pub(crate) fn from_api_error(
+        status: StatusCode,
+        response: ErrorResponse,
+        retry_after: Option<Duration>,
+    ) -> Self {
+        let message = response.error.to_lowercase();
+        let error = ApiError { status, response };
+
+        if status == StatusCode::TOO_MANY_REQUESTS || message.contains("rate limit") {
+            return Error::RateLimited { error, retry_after };
+        }
+
+        if status == StatusCode::PAYMENT_REQUIRED || message.contains("credits") {
+            return Error::InsufficientCredits(error);
+        }
+
+        match status {
+            StatusCode::UNAUTHORIZED => Error::Unauthorized(error),
+            StatusCode::FORBIDDEN => Error::PlanRestriction(error),
+            StatusCode::NOT_FOUND => Error::NotFound(error),
+            StatusCode::BAD_REQUEST => Error::InvalidQuery(error),
+            status if status.is_server_error() => Error::Server(error),
+            _ if message.contains("invalid api key") => Error::Unauthorized(error),
+            _ if message.contains("no information available") => Error::NotFound(error),
+            _ if message.contains("membership") || message.contains("upgrade") => {
+                Error::PlanRestriction(error)
+            }
+            _ => Error::Shodan(error),
+        }
+    }
+
+    /// The error response returned by the API, if this error originated from one.
+    pub fn api_error(&self) -> Option<&ApiError> {
+        match self {
+            Error::Unauthorized(e)
+            | Error::InsufficientCredits(e)
+            | Error::PlanRestriction(e)
+            | Error::RateLimited { error: e, .. }
+            | Error::NotFound(e)
+            | Error::InvalidQuery(e)
+            | Error::Server(e)
+            | Error::Shodan(e) => Some(e),
+            _ => None,
+        }
+    }
+
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            Error::Reqwest(e) => e.status(),
+            _ => self.api_error().map(|e| e.status),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ApiError {
+    pub status: StatusCode,
+    pub response: ErrorResponse,
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.response.error, self.status)
+    }
+}
+
+/// Reads `Retry-After` as either a number of seconds or an HTTP date to wait until.
+pub(crate) fn parse_retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let date = httpdate::parse_http_date(value).ok()?;
+    Some(
+        date.duration_since(SystemTime::now())
+            .unwrap_or(Duration::ZERO),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use reqwest::StatusCode;
+    use std::time::Duration;
+
+    fn classify(status: u16, message: &str) -> Error {
+        Error::from_api_error(
+            StatusCode::from_u16(status).unwrap(),
+            ErrorResponse {
+                error: message.into(),
+            },
+            None,
+        )
+    }
+
+    #[test]
+    fn classifies_by_status() {
+        assert!(matches!(
+            classify(401, "Invalid API key"),
+            Error::Unauthorized(_)
+        ));
+        assert!(matches!(
+            classify(403, "Access denied (403 Forbidden)"),
+            Error::PlanRestriction(_)
+        ));
+        assert!(matches!(
+            classify(404, "No information available for that IP."),
+            Error::NotFound(_)
+        ));
+        assert!(matches!(
+            classify(400, "Invalid search query"),
+            Error::InvalidQuery(_)
+        ));
+        assert!(matches!(classify(503, "Unavailable"), Error::Server(_)));
+        assert!(matches!(classify(418, "Teapot"), Error::Shodan(_)));
+    }
+
+    #[test]
+    fn classifies_by_message() {
+        assert!(matches!(
+            classify(
+                403,
+                "Insufficient query credits, please upgrade your API plan"
+            ),
+            Error::InsufficientCredits(_)
+        ));
+        assert!(matches!(
+            classify(200, "No information available for that IP."),
+            Error::NotFound(_)
+        ));
+        assert!(matches!(
+            classify(200, "Requires membership or higher to access"),
+            Error::PlanRestriction(_)
+        ));
+    }
+
+    #[test]
+    fn keeps_status_and_retry_after() {
+        let error = Error::from_api_error(
+            StatusCode::TOO_MANY_REQUESTS,
+            ErrorResponse {
+                error: "Rate limit reached".into(),
+            },
+            Some(Duration::from_secs(2)),
+        );
+
+        assert_eq!(error.status(), Some(StatusCode::TOO_MANY_REQUESTS));
+        assert_eq!(
+            error.api_error().unwrap().response.error,
+            "Rate limit reached"
+        );
+        assert!(matches!(
+            error,
+            Error::RateLimited {
+                retry_after: Some(d),
+                ..
+            } if d == Duration::from_secs(2)
+        ));
+    }
+
+    #[test]
+    fn reads_retry_after_seconds_and_dates() {
+        use crate::error::parse_retry_after;
+        use reqwest::header::{HeaderMap, HeaderValue, RETRY_AFTER};
+        use std::time::SystemTime;
+
+        let retry_after = |value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(RETRY_AFTER, HeaderValue::from_str(value).unwrap());
+            parse_retry_after(&headers)
+        };
+
+        assert_eq!(retry_after("7"), Some(Duration::from_secs(7)));
+        assert_eq!(
+            retry_after("Wed, 21 Oct 2015 07:28:00 GMT"),
+            Some(Duration::ZERO)
+        );
+        let later = httpdate::fmt_http_date(SystemTime::now() + Duration::from_secs(60));
+        let delay = retry_after(&later).unwrap();
+        assert!(delay > Duration::from_secs(55) && delay <= Duration::from_secs(60));
+        assert_eq!(retry_after("soon"), None);
+    }
 }
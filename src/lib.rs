@@ -1,38 +1,86 @@
+use reqwest::{Method, StatusCode};
+use serde::de::IgnoredAny;
 use serde::Deserialize;
 use std::collections::{hash_map, HashMap};
+use std::sync::Arc;
+use std::time::Duration;
 use url::Url;
 
 mod account;
+mod alerts;
 mod api_status;
 mod builders;
+mod bulk;
 mod directory;
 mod dns;
 mod error;
+mod notifiers;
+mod organization;
+mod query;
+mod rate_limit;
 mod response;
+mod retry;
 mod scanning;
 mod search;
+mod streaming;
+mod timeline;
+mod timestamp;
+mod transport;
 mod utility;
+mod validation;
+
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 
 pub use account::*;
+pub use alerts::*;
 pub use api_status::*;
 pub use builders::*;
+pub use bulk::*;
 pub use directory::*;
 pub use dns::*;
 pub use error::*;
+pub use ipnet::IpNet;
+pub use notifiers::*;
+pub use organization::*;
+pub use query::*;
+pub use rate_limit::RateLimit;
 pub use response::*;
+pub use retry::*;
 pub use scanning::*;
 pub use search::*;
+pub use streaming::*;
+pub use timeline::*;
+pub use transport::*;
 pub use utility::*;
+pub use validation::*;
 
 const BASE_API_URL: &str = "https://api.shodan.io";
+const BASE_STREAM_URL: &str = "https://stream.shodan.io";
 
+#[derive(Clone)]
 pub struct ShodanClient {
     api_key: String,
+    base_url: Url,
+    stream_url: Url,
+    /// Used directly for streams and downloads that shouldn't be buffered by the transport.
+    http_client: reqwest::Client,
+    transport: Arc<dyn Transport>,
+    rate_limiter: Option<rate_limit::RateLimiter>,
+    retry_policy: Option<RetryPolicy>,
+    /// Set when searches should be validated, filled in on the first search.
+    query_validator: Option<Arc<tokio::sync::OnceCell<QueryValidator>>>,
 }
 
 impl ShodanClient {
     pub fn new(api_key: String) -> Self {
-        Self { api_key }
+        Self::builder(api_key)
+            .build()
+            .expect("Default client configuration should always build")
+    }
+
+    pub fn builder(api_key: impl Into<String>) -> ShodanClientBuilder {
+        ShodanClientBuilder::new(api_key)
     }
 
     fn build_request_url(
@@ -40,8 +88,28 @@ impl ShodanClient {
         endpoint: &str,
         parameters: &ParameterBag,
     ) -> Result<String, error::Error> {
-        let mut url = Url::parse(BASE_API_URL)?;
-        url.set_path(endpoint);
+        self.build_url(&self.base_url, endpoint, parameters)
+    }
+
+    fn build_stream_url(
+        &self,
+        endpoint: &str,
+        parameters: &ParameterBag,
+    ) -> Result<String, error::Error> {
+        self.build_url(&self.stream_url, endpoint, parameters)
+    }
+
+    fn build_url(
+        &self,
+        base_url: &Url,
+        endpoint: &str,
+        parameters: &ParameterBag,
+    ) -> Result<String, error::Error> {
+        let mut url = base_url.clone();
+
+        // Keep any path prefix from the base URL (ex: when going through a gateway)
+        let path = format!("{}{}", base_url.path().trim_end_matches('/'), endpoint);
+        url.set_path(path.as_str());
 
         // Set API key
         url.query_pairs_mut()
@@ -53,19 +121,115 @@ impl ShodanClient {
         Ok(url.to_string())
     }
 
-    async fn fetch<T: for<'a> Deserialize<'a>>(url: String) -> Result<T, Error> {
-        let response = reqwest::get(url)
-            .await?
-            .json::<ShodanClientResponse<T>>()
+    /// Checks a query before it's used for a credit consuming call, if the client was built with
+    /// `validate_queries`.
+    async fn validate_query(&self, query: &str) -> Result<(), Error> {
+        let Some(validator) = &self.query_validator else {
+            return Ok(());
+        };
+
+        let validator = validator
+            .get_or_try_init(|| QueryValidator::fetch(self))
             .await?;
+        validator.validate(&Query::parse(query)?)
+    }
+
+    async fn fetch<T: for<'a> Deserialize<'a>>(&self, url: String) -> Result<T, Error> {
+        self.send(Method::GET, url, None, CallKind::Idempotent)
+            .await
+    }
 
-        match response {
-            ShodanClientResponse::Error(e) => {
-                Err(error::Error::Shodan(format!("Error response: {}", e.error)))
+    /// Same as `fetch` but for calls that consume query or scan credits. These are only retried
+    /// when the retry policy explicitly allows it.
+    async fn fetch_metered<T: for<'a> Deserialize<'a>>(&self, url: String) -> Result<T, Error> {
+        self.send(Method::GET, url, None, CallKind::Metered).await
+    }
+
+    /// Sends a request whose response carries nothing of interest, ex: `{"success": true}`.
+    async fn perform(
+        &self,
+        method: Method,
+        url: String,
+        body: Option<RequestBody>,
+    ) -> Result<(), Error> {
+        let _: IgnoredAny = self.send(method, url, body, CallKind::Idempotent).await?;
+
+        Ok(())
+    }
+
+    async fn send<T: for<'a> Deserialize<'a>>(
+        &self,
+        method: Method,
+        url: String,
+        body: Option<RequestBody>,
+        kind: CallKind,
+    ) -> Result<T, Error> {
+        let request = TransportRequest { method, url, body };
+
+        let mut attempt = 1;
+        loop {
+            let result = self.send_once(&request).await;
+
+            let delay = match (&result, &self.retry_policy) {
+                (Err(error), Some(policy)) => policy.delay_for(error, attempt, kind),
+                _ => None,
+            };
+
+            match delay {
+                Some(delay) => {
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                None => return result,
             }
+        }
+    }
+
+    async fn send_once<T: for<'a> Deserialize<'a>>(
+        &self,
+        request: &TransportRequest,
+    ) -> Result<T, Error> {
+        if let Some(rate_limiter) = &self.rate_limiter {
+            rate_limiter.acquire().await;
+        }
+
+        let response = self.transport.send(request.clone()).await?;
+        let retry_after = error::parse_retry_after(&response.headers);
+
+        Self::decode_response(response.status, retry_after, &response.body)
+    }
+
+    fn decode_response<T: for<'a> Deserialize<'a>>(
+        status: StatusCode,
+        retry_after: Option<Duration>,
+        body: &[u8],
+    ) -> Result<T, Error> {
+        if !status.is_success() {
+            return Err(Self::decode_error(status, retry_after, body));
+        }
+
+        // Some endpoints answer with an empty body on success
+        let body = if body.iter().all(u8::is_ascii_whitespace) {
+            &b"null"[..]
+        } else {
+            body
+        };
+
+        match serde_json::from_slice::<ShodanClientResponse<T>>(body)? {
+            ShodanClientResponse::Error(e) => Err(Error::from_api_error(status, e, retry_after)),
             ShodanClientResponse::Response(r) => Ok(r),
         }
     }
+
+    fn decode_error(status: StatusCode, retry_after: Option<Duration>, body: &[u8]) -> Error {
+        // Not every error comes with a JSON body, fall back to the raw body in that case
+        let response =
+            serde_json::from_slice::<ErrorResponse>(body).unwrap_or_else(|_| ErrorResponse {
+                error: String::from_utf8_lossy(body).trim().to_string(),
+            });
+
+        Error::from_api_error(status, response, retry_after)
+    }
 }
 
 #[derive(Default)]
@@ -89,6 +253,15 @@ impl ParameterBag {
     pub fn pairs(&self) -> hash_map::Iter<'_, String, String> {
         self.0.iter()
     }
+
+    /// Turns the parameters into a form body. Sorted so that identical forms always serialize
+    /// the same way.
+    pub fn into_form(self) -> RequestBody {
+        let mut pairs = self.0.into_iter().collect::<Vec<_>>();
+        pairs.sort();
+
+        RequestBody::Form(pairs)
+    }
 }
 
 pub struct ParameterValue(String);
@@ -119,13 +292,161 @@ impl From<bool> for ParameterValue {
 
 #[cfg(test)]
 mod tests {
+    use crate::testing::{MockResponse, MockServer};
+    use crate::*;
+    use reqwest::Method;
     use std::env;
+    use std::ops::Deref;
+
+    /// Client for the endpoint tests. Runs against the mock server and its fixtures unless a key
+    /// for the real API is supplied through `SHODAN_TEST_KEY`.
+    pub struct TestClient {
+        client: ShodanClient,
+        _server: Option<MockServer>,
+    }
 
-    pub fn get_test_api_key() -> String {
-        let api_key = env::var("SHODAN_TEST_KEY");
-        match api_key {
-            Ok(key) => key,
-            Err(_) => panic!("Did not specify a shodan API key for testing"),
+    impl Deref for TestClient {
+        type Target = ShodanClient;
+
+        fn deref(&self) -> &Self::Target {
+            &self.client
+        }
+    }
+
+    pub async fn test_client() -> TestClient {
+        match env::var("SHODAN_TEST_KEY") {
+            Ok(key) => TestClient {
+                client: ShodanClient::new(key),
+                _server: None,
+            },
+            Err(_) => {
+                let server = MockServer::with_fixtures().await;
+                TestClient {
+                    client: server.client(),
+                    _server: Some(server),
+                }
+            }
         }
     }
+
+    /// Banner `index` of the host search fixture with the top-level fields in `changes` swapped
+    /// in, a `null` removing the field altogether.
+    pub fn banner_json(index: usize, changes: serde_json::Value) -> serde_json::Value {
+        let fixture = serde_json::from_str::<serde_json::Value>(include_str!(
+            "../fixtures/shodan_host_search.json"
+        ))
+        .unwrap();
+
+        let mut banner = fixture["matches"][index].clone();
+        let object = banner.as_object_mut().unwrap();
+        for (key, value) in changes.as_object().unwrap() {
+            match value {
+                serde_json::Value::Null => object.remove(key),
+                value => object.insert(key.clone(), value.clone()),
+            };
+        }
+
+        banner
+    }
+
+    /// See `banner_json`.
+    pub fn banner(index: usize, changes: serde_json::Value) -> Banner {
+        serde_json::from_value(banner_json(index, changes)).unwrap()
+    }
+
+    #[tokio::test]
+    async fn retries_transient_failures() {
+        let server = MockServer::with_fixtures().await;
+        server.mock_once(
+            Method::GET,
+            "/api-info",
+            MockResponse::error(StatusCode::BAD_GATEWAY, "Bad gateway"),
+        );
+        server.mock_once(
+            Method::GET,
+            "/api-info",
+            MockResponse::error(StatusCode::TOO_MANY_REQUESTS, "Rate limit reached")
+                .header("Retry-After", "0"),
+        );
+
+        let client = ShodanClient::builder(testing::MOCK_API_KEY)
+            .base_url(server.url())
+            .retry_policy(
+                RetryPolicy::default()
+                    .initial_backoff(std::time::Duration::from_millis(1))
+                    .jitter(false),
+            )
+            .build()
+            .unwrap();
+
+        client.get_api_info().await.unwrap();
+        assert_eq!(server.received_requests().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn surfaces_errors_without_retry_policy() {
+        let server = MockServer::with_fixtures().await;
+        server.mock_once(
+            Method::GET,
+            "/shodan/host/8.8.8.8",
+            MockResponse::error(
+                StatusCode::NOT_FOUND,
+                "No information available for that IP.",
+            ),
+        );
+
+        let error = server
+            .client()
+            .host_ip("8.8.8.8".parse().unwrap(), None, None)
+            .await
+            .unwrap_err();
+
+        assert!(matches!(error, Error::NotFound(_)));
+        assert_eq!(server.received_requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn sends_api_key_and_parameters() {
+        let server = MockServer::with_fixtures().await;
+        server
+            .client()
+            .host_count(String::from("apache"), Some("os"))
+            .await
+            .unwrap();
+
+        let request = &server.received_requests()[0];
+        assert_eq!(request.path, "/shodan/host/count");
+        assert_eq!(request.query_param("key"), Some(testing::MOCK_API_KEY));
+        assert_eq!(request.query_param("query"), Some("apache"));
+        assert_eq!(request.query_param("facets"), Some("os"));
+    }
+
+    #[test]
+    fn decodes_error_bodies() {
+        let error = ShodanClient::decode_response::<ApiInfoResponse>(
+            StatusCode::NOT_FOUND,
+            None,
+            br#"{"error": "No information available for that IP."}"#,
+        )
+        .unwrap_err();
+        assert!(matches!(error, Error::NotFound(_)));
+
+        let error = ShodanClient::decode_response::<ApiInfoResponse>(
+            StatusCode::BAD_GATEWAY,
+            None,
+            b"<html>Bad Gateway</html>",
+        )
+        .unwrap_err();
+        assert!(
+            matches!(&error, Error::Server(e) if e.response.error == "<html>Bad Gateway</html>")
+        );
+    }
+
+    #[test]
+    fn decodes_successful_bodies() {
+        let ports =
+            ShodanClient::decode_response::<Vec<u16>>(StatusCode::OK, None, b"[22, 80]").unwrap();
+
+        assert_eq!(ports, vec![22, 80]);
+    }
 }
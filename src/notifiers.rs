@@ -0,0 +1,301 @@
+use crate::*;
+use async_trait::async_trait;
+use reqwest::Method;
+use serde::Deserialize;
+
+#[async_trait]
+pub trait Notifiers {
+    async fn get_notifiers(&self) -> Result<NotifiersResponse, Error>;
+
+    /// Lists the available notification providers along with the arguments they require.
+    async fn get_notifier_providers(&self) -> Result<HashMap<String, NotifierProvider>, Error>;
+
+    /// Creates a notifier and returns its id.
+    async fn create_notifier(
+        &self,
+        args: NotifierArgs,
+        description: Option<String>,
+    ) -> Result<String, Error>;
+
+    async fn get_notifier(&self, id: String) -> Result<Notifier, Error>;
+
+    /// Replaces the arguments of a notifier. The provider itself can't be changed.
+    async fn update_notifier(&self, id: String, args: NotifierArgs) -> Result<(), Error>;
+
+    async fn delete_notifier(&self, id: String) -> Result<(), Error>;
+}
+
+#[derive(Deserialize, Debug)]
+pub struct NotifiersResponse {
+    pub matches: Vec<Notifier>,
+    pub total: u32,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Notifier {
+    pub id: String,
+    pub provider: String,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub args: HashMap<String, String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct NotifierProvider {
+    pub required: Vec<String>,
+}
+
+/// Provider and arguments of a notifier. `Other` covers providers this client doesn't know
+/// about yet, check `get_notifier_providers` for the arguments they require.
+#[derive(Debug, Clone)]
+pub enum NotifierArgs {
+    Email {
+        to: String,
+    },
+    Slack {
+        webhook_url: String,
+    },
+    Webhook {
+        url: String,
+    },
+    Telegram {
+        chat_id: String,
+        token: String,
+    },
+    Pagerduty {
+        routing_key: String,
+    },
+    Pushover {
+        token: String,
+        user: String,
+    },
+    Other {
+        provider: String,
+        args: HashMap<String, String>,
+    },
+}
+
+impl NotifierArgs {
+    pub fn provider(&self) -> &str {
+        match self {
+            NotifierArgs::Email { .. } => "email",
+            NotifierArgs::Slack { .. } => "slack",
+            NotifierArgs::Webhook { .. } => "webhook",
+            NotifierArgs::Telegram { .. } => "telegram",
+            NotifierArgs::Pagerduty { .. } => "pagerduty",
+            NotifierArgs::Pushover { .. } => "pushover",
+            NotifierArgs::Other { provider, .. } => provider.as_str(),
+        }
+    }
+
+    fn set_parameters(&self, parameters: &mut ParameterBag) {
+        match self {
+            NotifierArgs::Email { to } => parameters.set("to", to),
+            NotifierArgs::Slack { webhook_url } => parameters.set("webhook_url", webhook_url),
+            NotifierArgs::Webhook { url } => parameters.set("url", url),
+            NotifierArgs::Telegram { chat_id, token } => {
+                parameters.set("chat_id", chat_id);
+                parameters.set("token", token);
+            }
+            NotifierArgs::Pagerduty { routing_key } => parameters.set("routing_key", routing_key),
+            NotifierArgs::Pushover { token, user } => {
+                parameters.set("token", token);
+                parameters.set("user", user);
+            }
+            NotifierArgs::Other { args, .. } => {
+                for (key, value) in args {
+                    parameters.set(key, value);
+                }
+            }
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct CreateNotifierResponse {
+    id: String,
+}
+
+#[async_trait]
+impl Notifiers for ShodanClient {
+    async fn get_notifiers(&self) -> Result<NotifiersResponse, Error> {
+        self.fetch(self.build_request_url("/notifier", &Default::default())?)
+            .await
+    }
+
+    async fn get_notifier_providers(&self) -> Result<HashMap<String, NotifierProvider>, Error> {
+        self.fetch(self.build_request_url("/notifier/provider", &Default::default())?)
+            .await
+    }
+
+    async fn create_notifier(
+        &self,
+        args: NotifierArgs,
+        description: Option<String>,
+    ) -> Result<String, Error> {
+        let mut parameters = ParameterBag::default();
+        parameters.set("provider", args.provider());
+        parameters.set_optional("description", description);
+        args.set_parameters(&mut parameters);
+
+        let response: CreateNotifierResponse = self
+            .send(
+                Method::POST,
+                self.build_request_url("/notifier", &Default::default())?,
+                Some(parameters.into_form()),
+                CallKind::Create,
+            )
+            .await?;
+
+        Ok(response.id)
+    }
+
+    async fn get_notifier(&self, id: String) -> Result<Notifier, Error> {
+        self.fetch(self.build_request_url(format!("/notifier/{id}").as_str(), &Default::default())?)
+            .await
+    }
+
+    async fn update_notifier(&self, id: String, args: NotifierArgs) -> Result<(), Error> {
+        let mut parameters = ParameterBag::default();
+        args.set_parameters(&mut parameters);
+
+        self.perform(
+            Method::PUT,
+            self.build_request_url(format!("/notifier/{id}").as_str(), &Default::default())?,
+            Some(parameters.into_form()),
+        )
+        .await
+    }
+
+    async fn delete_notifier(&self, id: String) -> Result<(), Error> {
+        self.perform(
+            Method::DELETE,
+            self.build_request_url(format!("/notifier/{id}").as_str(), &Default::default())?,
+            None,
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::{MockResponse, MockServer};
+    use crate::tests::test_client;
+    use crate::*;
+    use reqwest::Method;
+
+    const NOTIFIER_ID: &str = "7xkHQ2SNS4xmH3Ka";
+
+    fn form(body: &[u8]) -> Vec<(String, String)> {
+        url::form_urlencoded::parse(body).into_owned().collect()
+    }
+
+    #[tokio::test]
+    async fn can_get_notifiers() {
+        let client = test_client().await;
+        client.get_notifiers().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn can_get_notifier_providers() {
+        let client = test_client().await;
+        client.get_notifier_providers().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn can_create_notifier() {
+        let server = MockServer::with_fixtures().await;
+        let id = server
+            .client()
+            .create_notifier(
+                NotifierArgs::Slack {
+                    webhook_url: String::from("https://hooks.slack.com/services/T0/B0/X"),
+                },
+                Some(String::from("SOC channel")),
+            )
+            .await
+            .unwrap();
+        assert_eq!(id, NOTIFIER_ID);
+
+        let request = &server.received_requests()[0];
+        assert_eq!(
+            form(&request.body),
+            vec![
+                (String::from("description"), String::from("SOC channel")),
+                (String::from("provider"), String::from("slack")),
+                (
+                    String::from("webhook_url"),
+                    String::from("https://hooks.slack.com/services/T0/B0/X")
+                ),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn doesnt_retry_notifier_creation() {
+        let server = MockServer::with_fixtures().await;
+        server.mock_once(
+            Method::POST,
+            "/notifier",
+            MockResponse::error(reqwest::StatusCode::BAD_GATEWAY, "Bad gateway"),
+        );
+
+        let client = ShodanClient::builder(testing::MOCK_API_KEY)
+            .base_url(server.url())
+            .retry_policy(RetryPolicy::default().initial_backoff(std::time::Duration::ZERO))
+            .build()
+            .unwrap();
+
+        let error = client
+            .create_notifier(
+                NotifierArgs::Email {
+                    to: String::from("security@example.com"),
+                },
+                None,
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(error, Error::Server(_)));
+        assert_eq!(server.received_requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn can_manage_notifier() {
+        let server = MockServer::with_fixtures().await;
+        let client = server.client();
+
+        let notifier = client
+            .get_notifier(String::from(NOTIFIER_ID))
+            .await
+            .unwrap();
+        assert_eq!(
+            notifier.args["webhook_url"],
+            "https://hooks.slack.com/services/T0/B0/X"
+        );
+
+        client
+            .update_notifier(
+                String::from(NOTIFIER_ID),
+                NotifierArgs::Slack {
+                    webhook_url: String::from("https://hooks.slack.com/services/T0/B0/Y"),
+                },
+            )
+            .await
+            .unwrap();
+        client
+            .delete_notifier(String::from(NOTIFIER_ID))
+            .await
+            .unwrap();
+
+        let requests = server.received_requests();
+        assert_eq!(requests[1].method, Method::PUT);
+        assert_eq!(
+            form(&requests[1].body),
+            vec![(
+                String::from("webhook_url"),
+                String::from("https://hooks.slack.com/services/T0/B0/Y")
+            )]
+        );
+        assert_eq!(requests[2].method, Method::DELETE);
+    }
+}
@@ -0,0 +1,109 @@
+use crate::*;
+use async_trait::async_trait;
+use reqwest::Method;
+use serde::Deserialize;
+
+#[async_trait]
+pub trait Organization {
+    async fn get_org(&self) -> Result<OrgInfo, Error>;
+
+    /// Adds a user, by username or email, to the organization. Shodan emails them about it when
+    /// `notify` is set.
+    async fn add_org_member(&self, user: String, notify: Option<bool>) -> Result<(), Error>;
+
+    async fn remove_org_member(&self, user: String) -> Result<(), Error>;
+}
+
+#[derive(Deserialize, Debug)]
+pub struct OrgInfo {
+    pub id: String,
+    pub name: String,
+    pub created: String,
+    pub admins: Vec<OrgMember>,
+    pub members: Vec<OrgMember>,
+    /// Plan the members get upgraded to, ex: `enterprise`.
+    pub upgrade_type: String,
+    #[serde(default)]
+    pub domains: Vec<String>,
+    #[serde(default)]
+    pub logo: bool,
+}
+
+#[cfg(feature = "chrono")]
+impl OrgInfo {
+    /// When the organization was created.
+    pub fn created_utc(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        crate::timestamp::parse(&self.created)
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct OrgMember {
+    /// Missing for invited users that haven't created an account yet.
+    pub username: Option<String>,
+    pub email: String,
+}
+
+#[async_trait]
+impl Organization for ShodanClient {
+    async fn get_org(&self) -> Result<OrgInfo, Error> {
+        self.fetch(self.build_request_url("/org", &Default::default())?)
+            .await
+    }
+
+    async fn add_org_member(&self, user: String, notify: Option<bool>) -> Result<(), Error> {
+        let mut parameters = ParameterBag::default();
+        parameters.set_optional("notify", notify);
+
+        self.perform(
+            Method::PUT,
+            self.build_request_url(format!("/org/member/{user}").as_str(), &parameters)?,
+            None,
+        )
+        .await
+    }
+
+    async fn remove_org_member(&self, user: String) -> Result<(), Error> {
+        self.perform(
+            Method::DELETE,
+            self.build_request_url(format!("/org/member/{user}").as_str(), &Default::default())?,
+            None,
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::MockServer;
+    use crate::tests::test_client;
+    use crate::*;
+    use reqwest::Method;
+
+    #[tokio::test]
+    async fn can_get_org() {
+        let client = test_client().await;
+        client.get_org().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn can_manage_org_members() {
+        let server = MockServer::with_fixtures().await;
+        let client = server.client();
+
+        client
+            .add_org_member(String::from("asmith"), Some(true))
+            .await
+            .unwrap();
+        client
+            .remove_org_member(String::from("asmith"))
+            .await
+            .unwrap();
+
+        let requests = server.received_requests();
+        assert_eq!(requests[0].method, Method::PUT);
+        assert_eq!(requests[0].query_param("notify"), Some("true"));
+        assert_eq!(requests[1].method, Method::DELETE);
+        assert_eq!(requests[1].path, "/org/member/asmith");
+    }
+}
@@ -0,0 +1,333 @@
+use crate::*;
+use std::fmt;
+use std::iter::Peekable;
+use std::str::CharIndices;
+
+/// A search query broken up into its terms, as parsed by `Query::parse`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Query {
+    pub terms: Vec<QueryTerm>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QueryTerm {
+    /// Free text, quoted phrases are kept as a single term without the quotes.
+    Text(String),
+    Filter(QueryFilter),
+}
+
+/// A filter like `port:22,23` or `-product:"Apache httpd"`. Values are stored unquoted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryFilter {
+    pub name: String,
+    pub values: Vec<String>,
+    pub negated: bool,
+}
+
+impl Query {
+    /// Parses a query string, failing with `Error::QuerySyntax` holding the same kind of
+    /// messages `host_tokens` reports in `TokenResponse::errors`.
+    pub fn parse(query: &str) -> Result<Self, Error> {
+        let (terms, errors) = Parser::new(query).parse();
+        if !errors.is_empty() {
+            return Err(Error::QuerySyntax(errors));
+        }
+
+        Ok(Self { terms })
+    }
+
+    /// Lists the syntax errors in a query without calling the API, empty if the query is fine.
+    pub fn errors(query: &str) -> Vec<String> {
+        Parser::new(query).parse().1
+    }
+
+    pub fn text(&self) -> impl Iterator<Item = &str> {
+        self.terms.iter().filter_map(|term| match term {
+            QueryTerm::Text(text) => Some(text.as_str()),
+            QueryTerm::Filter(_) => None,
+        })
+    }
+
+    pub fn filters(&self) -> impl Iterator<Item = &QueryFilter> {
+        self.terms.iter().filter_map(|term| match term {
+            QueryTerm::Filter(filter) => Some(filter),
+            QueryTerm::Text(_) => None,
+        })
+    }
+}
+
+/// Free text ends up in front of the filters and the filters are sorted by name, like the
+/// builder always does, so the original order of the terms isn't kept.
+impl From<&Query> for SearchQueryBuilder {
+    fn from(query: &Query) -> Self {
+        let text = query
+            .text()
+            .map(|text| quote_value(text.to_string()))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        query.filters().fold(
+            SearchQueryBuilder::default().query(text),
+            |builder, filter| {
+                let add = |builder: SearchQueryBuilder| {
+                    filter.values.iter().fold(builder, |builder, value| {
+                        match (filter.name.as_str(), parse_geo(value)) {
+                            ("geo", Some((latitude, longitude, radius))) => {
+                                builder.geo(latitude, longitude, radius)
+                            }
+                            _ => builder.filter(&filter.name, value),
+                        }
+                    })
+                };
+
+                if filter.negated {
+                    builder.not(add)
+                } else {
+                    add(builder)
+                }
+            },
+        )
+    }
+}
+
+/// Splits a `geo` value into its latitude, longitude and optional radius.
+pub(crate) fn parse_geo(value: &str) -> Option<(f64, f64, Option<u32>)> {
+    let parts = value.split(',').map(str::trim).collect::<Vec<_>>();
+    let (latitude, longitude, radius) = match parts[..] {
+        [latitude, longitude] => (latitude, longitude, None),
+        [latitude, longitude, radius] => (latitude, longitude, Some(radius.parse().ok()?)),
+        _ => return None,
+    };
+
+    Some((latitude.parse().ok()?, longitude.parse().ok()?, radius))
+}
+
+impl From<Query> for SearchQueryBuilder {
+    fn from(query: Query) -> Self {
+        Self::from(&query)
+    }
+}
+
+/// Formats the query the way `SearchQueryBuilder` would build it, with the free text first.
+impl fmt::Display for Query {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&SearchQueryBuilder::from(self).build())
+    }
+}
+
+impl std::str::FromStr for Query {
+    type Err = Error;
+
+    fn from_str(query: &str) -> Result<Self, Self::Err> {
+        Self::parse(query)
+    }
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    chars: Peekable<CharIndices<'a>>,
+    errors: Vec<String>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(input: &'a str) -> Self {
+        Self {
+            input,
+            chars: input.char_indices().peekable(),
+            errors: vec![],
+        }
+    }
+
+    fn parse(mut self) -> (Vec<QueryTerm>, Vec<String>) {
+        let mut terms = vec![];
+        while let Some(term) = self.term() {
+            terms.extend(term);
+        }
+
+        (terms, self.errors)
+    }
+
+    /// Returns `None` once the input runs out, `Some(None)` for terms that couldn't be parsed.
+    fn term(&mut self) -> Option<Option<QueryTerm>> {
+        while self.chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+        let &(start, first) = self.chars.peek()?;
+
+        if first == '"' {
+            return Some(self.quoted().map(QueryTerm::Text));
+        }
+
+        let word = self.take_while(|c| !c.is_whitespace() && c != ':' && c != '"');
+        if self.chars.next_if(|(_, c)| *c == ':').is_none() {
+            // Not a filter after all, so the whole word is free text
+            let rest = self.take_while(|c| !c.is_whitespace());
+            return Some(Some(QueryTerm::Text(format!("{word}{rest}"))));
+        }
+
+        let (negated, name) = match word.strip_prefix('-') {
+            Some(name) => (true, name),
+            None => (false, word),
+        };
+        if name.is_empty() {
+            self.errors
+                .push(format!("Missing filter name at position {start}"));
+            self.take_while(|c| !c.is_whitespace());
+            return Some(None);
+        }
+
+        Some(self.values(name).map(|values| {
+            QueryTerm::Filter(QueryFilter {
+                name: name.to_string(),
+                values,
+                negated,
+            })
+        }))
+    }
+
+    fn values(&mut self, name: &str) -> Option<Vec<String>> {
+        let mut values = vec![];
+        loop {
+            if let Some((_, '"')) = self.chars.peek() {
+                values.push(self.quoted()?);
+            } else {
+                // The coordinates of a geo filter are a single value, not alternatives
+                let split = name != "geo";
+                let value = self.take_while(|c| !c.is_whitespace() && (c != ',' || !split));
+                if value.is_empty() {
+                    self.errors.push(format!("Missing value for filter {name}"));
+                    self.take_while(|c| !c.is_whitespace());
+                    return None;
+                }
+                values.push(value.to_string());
+            }
+
+            if self.chars.next_if(|(_, c)| *c == ',').is_none() {
+                return Some(values);
+            }
+        }
+    }
+
+    /// Reads a double quoted string, the opening quote being the next character.
+    fn quoted(&mut self) -> Option<String> {
+        let (start, _) = self.chars.next()?;
+
+        let mut value = String::new();
+        while let Some((_, c)) = self.chars.next() {
+            match c {
+                '"' => return Some(value),
+                '\\' => value.extend(self.chars.next().map(|(_, c)| c)),
+                c => value.push(c),
+            }
+        }
+
+        self.errors
+            .push(format!("Unterminated quote at position {start}"));
+        None
+    }
+
+    fn take_while(&mut self, predicate: impl Fn(char) -> bool) -> &'a str {
+        let start = self.position();
+        while self.chars.next_if(|(_, c)| predicate(*c)).is_some() {}
+
+        &self.input[start..self.position()]
+    }
+
+    fn position(&mut self) -> usize {
+        self.chars
+            .peek()
+            .map(|(index, _)| *index)
+            .unwrap_or(self.input.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    fn filter(name: &str, values: &[&str], negated: bool) -> QueryTerm {
+        QueryTerm::Filter(QueryFilter {
+            name: name.to_string(),
+            values: values.iter().map(|value| value.to_string()).collect(),
+            negated,
+        })
+    }
+
+    #[test]
+    fn can_parse_query() {
+        let query = Query::parse(
+            r#"nginx "default page" port:80,443 -product:"Apache httpd" geo:52.37,4.89"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            query.terms,
+            vec![
+                QueryTerm::Text(String::from("nginx")),
+                QueryTerm::Text(String::from("default page")),
+                filter("port", &["80", "443"], false),
+                filter("product", &["Apache httpd"], true),
+                filter("geo", &["52.37,4.89"], false),
+            ]
+        );
+    }
+
+    #[test]
+    fn can_parse_escaped_quotes() {
+        let query = Query::parse(r#"http.title:"Say \"hi\", world""#).unwrap();
+
+        assert_eq!(
+            query.terms,
+            vec![filter("http.title", &[r#"Say "hi", world"#], false)]
+        );
+    }
+
+    #[test]
+    fn round_trips_through_builder() {
+        let built = SearchQueryBuilder::default()
+            .query("nginx")
+            .product("Apache httpd")
+            .http(|http| http.title("Say \"hi\", world"))
+            .not(|q| q.port(22).port(23))
+            .build();
+
+        let query = Query::parse(&built).unwrap();
+        assert_eq!(query.to_string(), built);
+        assert_eq!(Query::parse(&query.to_string()).unwrap(), query);
+    }
+
+    #[test]
+    fn round_trips_parsed_queries() {
+        let query =
+            Query::parse(r#"port:22 nginx -country:CN geo:52.37,4.89,50 "default page""#).unwrap();
+
+        let built = query.to_string();
+        assert_eq!(
+            built,
+            r#"nginx "default page" geo:52.37,4.89,50 port:22 -country:CN"#
+        );
+
+        // Only the order of the terms changes
+        let mut terms = query.terms;
+        let mut reparsed = Query::parse(&built).unwrap().terms;
+        let key = |term: &QueryTerm| format!("{term:?}");
+        terms.sort_by_key(key);
+        reparsed.sort_by_key(key);
+        assert_eq!(reparsed, terms);
+    }
+
+    #[test]
+    fn reports_syntax_errors() {
+        assert!(Query::errors("apache port:22 country:NL").is_empty());
+        assert_eq!(
+            Query::errors(r#"port: :80 product:"Apache"#),
+            vec![
+                "Missing value for filter port",
+                "Missing filter name at position 6",
+                "Unterminated quote at position 18",
+            ]
+        );
+        assert!(matches!(
+            Query::parse("port:22,"),
+            Err(Error::QuerySyntax(errors)) if errors == vec!["Missing value for filter port"]
+        ));
+    }
+}
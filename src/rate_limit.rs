@@ -0,0 +1,169 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Request rate the client throttles itself to. Defaults to Shodan's policy of 1 request per
+/// second without any burst.
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimit {
+    requests: u32,
+    per: Duration,
+    burst: u32,
+}
+
+impl RateLimit {
+    pub fn new(requests: u32, per: Duration) -> Self {
+        Self {
+            requests: requests.max(1),
+            per,
+            burst: 1,
+        }
+    }
+
+    pub fn per_second(requests: u32) -> Self {
+        Self::new(requests, Duration::from_secs(1))
+    }
+
+    /// Amount of requests that can go out back-to-back after the client has been idle.
+    pub fn burst(mut self, burst: u32) -> Self {
+        self.burst = burst.max(1);
+
+        self
+    }
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        Self::per_second(1)
+    }
+}
+
+/// Token bucket shared between all clones of a client.
+#[derive(Clone)]
+pub(crate) struct RateLimiter {
+    bucket: Arc<Mutex<Bucket>>,
+    interval: Duration,
+    burst: f64,
+}
+
+struct Bucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(limit: RateLimit) -> Self {
+        Self {
+            bucket: Arc::new(Mutex::new(Bucket {
+                tokens: limit.burst as f64,
+                last_refill: Instant::now(),
+            })),
+            interval: limit.per / limit.requests,
+            burst: limit.burst as f64,
+        }
+    }
+
+    /// Waits until a request is allowed to go out.
+    pub(crate) async fn acquire(&self) {
+        let wait = {
+            let mut bucket = self.bucket.lock().unwrap();
+
+            let now = Instant::now();
+            let refilled = (now - bucket.last_refill).as_secs_f64() / self.interval.as_secs_f64();
+            bucket.tokens = (bucket.tokens + refilled).min(self.burst);
+            bucket.last_refill = now;
+
+            // Take the token up front, callers that have to wait queue up behind each other by
+            // pushing the bucket further into the negative. Callers that give up hand it back.
+            bucket.tokens -= 1.0;
+            if bucket.tokens < 0.0 {
+                let reservation = Reservation {
+                    bucket: &self.bucket,
+                };
+                Some((self.interval.mul_f64(-bucket.tokens), reservation))
+            } else {
+                None
+            }
+        };
+
+        if let Some((wait, reservation)) = wait {
+            tokio::time::sleep(wait).await;
+            std::mem::forget(reservation);
+        }
+    }
+}
+
+/// Token taken by a caller that is still waiting for its turn. Handed back to the bucket if
+/// the caller gives up before then, ex: when the request future is dropped.
+struct Reservation<'a> {
+    bucket: &'a Mutex<Bucket>,
+}
+
+impl Drop for Reservation<'_> {
+    fn drop(&mut self) {
+        self.bucket.lock().unwrap().tokens += 1.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rate_limit::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn spaces_out_requests() {
+        let limiter = RateLimiter::new(RateLimit::per_second(1));
+        let start = Instant::now();
+
+        for _ in 0..3 {
+            limiter.acquire().await;
+        }
+
+        assert_eq!(start.elapsed(), Duration::from_secs(2));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn allows_bursts() {
+        let limiter = RateLimiter::new(RateLimit::per_second(2).burst(3));
+        let start = Instant::now();
+
+        for _ in 0..3 {
+            limiter.acquire().await;
+        }
+        assert_eq!(start.elapsed(), Duration::ZERO);
+
+        limiter.acquire().await;
+        assert_eq!(start.elapsed(), Duration::from_millis(500));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn is_shared_between_tasks() {
+        let limiter = RateLimiter::new(RateLimit::per_second(1));
+        let start = Instant::now();
+
+        let tasks = (0..4)
+            .map(|_| {
+                let limiter = limiter.clone();
+                tokio::spawn(async move { limiter.acquire().await })
+            })
+            .collect::<Vec<_>>();
+
+        for task in tasks {
+            task.await.unwrap();
+        }
+
+        assert_eq!(start.elapsed(), Duration::from_secs(3));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn returns_tokens_of_cancelled_callers() {
+        let limiter = RateLimiter::new(RateLimit::per_second(1));
+        let start = Instant::now();
+
+        limiter.acquire().await;
+        let cancelled = tokio::time::timeout(Duration::from_millis(100), limiter.acquire()).await;
+        assert!(cancelled.is_err());
+
+        limiter.acquire().await;
+        assert_eq!(start.elapsed(), Duration::from_secs(1));
+    }
+}
@@ -7,7 +7,7 @@ pub enum ShodanClientResponse<T> {
     Response(T),
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 pub struct ErrorResponse {
     pub error: String,
 }
@@ -0,0 +1,333 @@
+use crate::Error;
+use std::time::Duration;
+
+/// Classes of failures a `RetryPolicy` can retry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RetryOn {
+    /// The connection couldn't be established or was dropped while sending the request or
+    /// reading the response.
+    Connection,
+    /// The request timed out.
+    Timeout,
+    /// Shodan responded with a rate limiting error.
+    RateLimited,
+    /// Shodan responded with a 5xx status.
+    ServerError,
+}
+
+impl RetryOn {
+    fn classify(error: &Error) -> Option<Self> {
+        match error {
+            Error::Reqwest(e) if e.is_timeout() => Some(RetryOn::Timeout),
+            Error::Reqwest(e) if e.is_connect() || e.is_request() || e.is_body() => {
+                Some(RetryOn::Connection)
+            }
+            Error::StreamTimeout(_) => Some(RetryOn::Timeout),
+            Error::StreamClosed => Some(RetryOn::Connection),
+            Error::RateLimited { .. } => Some(RetryOn::RateLimited),
+            Error::Server(_) => Some(RetryOn::ServerError),
+            _ => None,
+        }
+    }
+
+    /// Whether the error shows the request never made it to shodan or was turned away before
+    /// anything happened.
+    fn never_processed(error: &Error) -> bool {
+        match error {
+            Error::Reqwest(e) => e.is_connect(),
+            Error::RateLimited { .. } => true,
+            _ => false,
+        }
+    }
+}
+
+/// What repeating a call could cost, decides which failures of it may be retried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum CallKind {
+    /// Repeating the call has no side effects.
+    Idempotent,
+    /// The call consumes query or scan credits.
+    Metered,
+    /// The call creates something, so it's only retried when shodan certainly never processed
+    /// it. A retried create that did go through the first time would leave a duplicate behind.
+    Create,
+}
+
+/// Describes how failed calls are retried. Calls that consume query or scan credits are never
+/// retried unless `retry_credit_consuming` is enabled, as a failed attempt might still have
+/// been billed. Calls that create something, ex: `create_alert`, are only retried when the
+/// connection couldn't be established or shodan rate limited them.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    multiplier: f64,
+    jitter: bool,
+    respect_retry_after: bool,
+    retry_on: Vec<RetryOn>,
+    retry_credit_consuming: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            multiplier: 2.0,
+            jitter: true,
+            respect_retry_after: true,
+            retry_on: vec![
+                RetryOn::Connection,
+                RetryOn::Timeout,
+                RetryOn::RateLimited,
+                RetryOn::ServerError,
+            ],
+            retry_credit_consuming: false,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Total amount of attempts, including the first one.
+    pub fn max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+
+        self
+    }
+
+    pub fn initial_backoff(mut self, backoff: Duration) -> Self {
+        self.initial_backoff = backoff;
+
+        self
+    }
+
+    pub fn max_backoff(mut self, backoff: Duration) -> Self {
+        self.max_backoff = backoff;
+
+        self
+    }
+
+    pub fn multiplier(mut self, multiplier: f64) -> Self {
+        self.multiplier = multiplier.max(1.0);
+
+        self
+    }
+
+    /// Randomizes each delay between half and the full backoff so that concurrent callers
+    /// don't all retry at the same time.
+    pub fn jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+
+        self
+    }
+
+    /// Waits for the duration in the `Retry-After` header instead of the computed backoff
+    /// when shodan supplies one, up to `max_backoff`.
+    pub fn respect_retry_after(mut self, respect: bool) -> Self {
+        self.respect_retry_after = respect;
+
+        self
+    }
+
+    pub fn retry_on(mut self, retry_on: impl IntoIterator<Item = RetryOn>) -> Self {
+        self.retry_on = retry_on.into_iter().collect();
+
+        self
+    }
+
+    pub fn retry_credit_consuming(mut self, retry: bool) -> Self {
+        self.retry_credit_consuming = retry;
+
+        self
+    }
+
+    /// Returns how long to wait before the next attempt, or `None` if the error should be
+    /// returned to the caller. `attempt` is the 1-based number of the attempt that failed.
+    pub(crate) fn delay_for(
+        &self,
+        error: &Error,
+        attempt: u32,
+        kind: CallKind,
+    ) -> Option<Duration> {
+        if attempt >= self.max_attempts {
+            return None;
+        }
+
+        match kind {
+            CallKind::Idempotent => {}
+            CallKind::Metered if self.retry_credit_consuming => {}
+            CallKind::Metered => return None,
+            CallKind::Create if RetryOn::never_processed(error) => {}
+            CallKind::Create => return None,
+        }
+
+        let class = RetryOn::classify(error)?;
+        if !self.retry_on.contains(&class) {
+            return None;
+        }
+
+        if let Error::RateLimited {
+            retry_after: Some(retry_after),
+            ..
+        } = error
+        {
+            if self.respect_retry_after {
+                return Some((*retry_after).min(self.max_backoff));
+            }
+        }
+
+        Some(self.backoff(attempt))
+    }
+
+    fn backoff(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(i32::MAX as u32) as i32;
+        let backoff = self
+            .initial_backoff
+            .mul_f64(self.multiplier.powi(exponent))
+            .min(self.max_backoff);
+
+        if self.jitter {
+            backoff.mul_f64(0.5 + fastrand::f64() / 2.0)
+        } else {
+            backoff
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use reqwest::StatusCode;
+    use std::time::Duration;
+
+    fn api_error(status: StatusCode, retry_after: Option<Duration>) -> Error {
+        Error::from_api_error(
+            status,
+            ErrorResponse {
+                error: "Something went wrong".into(),
+            },
+            retry_after,
+        )
+    }
+
+    #[test]
+    fn backs_off_exponentially() {
+        let policy = RetryPolicy::default()
+            .max_attempts(5)
+            .initial_backoff(Duration::from_secs(1))
+            .max_backoff(Duration::from_secs(3))
+            .jitter(false);
+        let error = api_error(StatusCode::BAD_GATEWAY, None);
+
+        assert_eq!(
+            policy.delay_for(&error, 1, CallKind::Idempotent),
+            Some(Duration::from_secs(1))
+        );
+        assert_eq!(
+            policy.delay_for(&error, 2, CallKind::Idempotent),
+            Some(Duration::from_secs(2))
+        );
+        assert_eq!(
+            policy.delay_for(&error, 3, CallKind::Idempotent),
+            Some(Duration::from_secs(3))
+        );
+        assert_eq!(policy.delay_for(&error, 5, CallKind::Idempotent), None);
+    }
+
+    #[test]
+    fn jitters_within_bounds() {
+        let policy = RetryPolicy::default().initial_backoff(Duration::from_secs(2));
+        let error = api_error(StatusCode::BAD_GATEWAY, None);
+
+        for _ in 0..100 {
+            let delay = policy.delay_for(&error, 1, CallKind::Idempotent).unwrap();
+            assert!(delay >= Duration::from_secs(1) && delay <= Duration::from_secs(2));
+        }
+    }
+
+    #[test]
+    fn respects_retry_after() {
+        let error = api_error(StatusCode::TOO_MANY_REQUESTS, Some(Duration::from_secs(7)));
+
+        assert_eq!(
+            RetryPolicy::default().delay_for(&error, 1, CallKind::Idempotent),
+            Some(Duration::from_secs(7))
+        );
+        assert_ne!(
+            RetryPolicy::default().respect_retry_after(false).delay_for(
+                &error,
+                1,
+                CallKind::Idempotent
+            ),
+            Some(Duration::from_secs(7))
+        );
+        assert_eq!(
+            RetryPolicy::default()
+                .max_backoff(Duration::from_secs(5))
+                .delay_for(&error, 1, CallKind::Idempotent),
+            Some(Duration::from_secs(5))
+        );
+    }
+
+    #[test]
+    fn only_retries_configured_classes() {
+        let policy = RetryPolicy::default().retry_on([RetryOn::RateLimited]);
+
+        assert!(policy
+            .delay_for(
+                &api_error(StatusCode::BAD_GATEWAY, None),
+                1,
+                CallKind::Idempotent
+            )
+            .is_none());
+        assert!(policy
+            .delay_for(
+                &api_error(StatusCode::TOO_MANY_REQUESTS, None),
+                1,
+                CallKind::Idempotent
+            )
+            .is_some());
+        assert!(policy
+            .delay_for(
+                &api_error(StatusCode::UNAUTHORIZED, None),
+                1,
+                CallKind::Idempotent
+            )
+            .is_none());
+    }
+
+    #[test]
+    fn skips_credit_consuming_calls_unless_enabled() {
+        let error = api_error(StatusCode::BAD_GATEWAY, None);
+
+        assert!(RetryPolicy::default()
+            .delay_for(&error, 1, CallKind::Metered)
+            .is_none());
+        assert!(RetryPolicy::default()
+            .retry_credit_consuming(true)
+            .delay_for(&error, 1, CallKind::Metered)
+            .is_some());
+    }
+
+    #[test]
+    fn only_retries_creates_that_never_went_through() {
+        let policy = RetryPolicy::default();
+
+        assert!(policy
+            .delay_for(
+                &api_error(StatusCode::BAD_GATEWAY, None),
+                1,
+                CallKind::Create
+            )
+            .is_none());
+        assert!(policy
+            .delay_for(
+                &api_error(StatusCode::TOO_MANY_REQUESTS, None),
+                1,
+                CallKind::Create
+            )
+            .is_some());
+    }
+}
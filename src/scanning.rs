@@ -1,8 +1,11 @@
 use crate::error::Error;
 use crate::response::ShodanClientResponse;
-use crate::ShodanClient;
+use crate::{CallKind, ParameterBag, ShodanClient};
 use async_trait::async_trait;
+use reqwest::{Method, StatusCode};
+use serde::Deserialize;
 use std::collections::HashMap;
+use std::time::Duration;
 
 #[async_trait]
 pub trait Scanning {
@@ -11,35 +14,450 @@ pub trait Scanning {
     async fn get_scanning_protocols(
         &self,
     ) -> Result<ShodanClientResponse<HashMap<String, String>>, Error>;
+
+    /// Requests shodan to crawl the given IPs or netblocks. Consumes 1 scan credit per IP.
+    async fn submit_scan(&self, targets: Vec<ScanTarget>) -> Result<ScanResponse, Error>;
+
+    /// Requests shodan to crawl the entire internet for a port and protocol combination. Only
+    /// available on enterprise plans. The protocol is checked against `get_scanning_protocols`
+    /// before submitting. Returns the id of the scan.
+    async fn scan_internet(&self, port: u16, protocol: String) -> Result<String, Error>;
+
+    async fn get_scans(&self) -> Result<ScansResponse, Error>;
+
+    async fn get_scan_status(&self, id: String) -> Result<ScanStatusResponse, Error>;
+
+    /// Polls the scan status every `interval` until the scan is done. Fails with
+    /// `Error::ScanTimeout` if it still isn't done when checked at the end of `timeout`.
+    async fn wait_for_scan(
+        &self,
+        id: String,
+        interval: Duration,
+        timeout: Duration,
+    ) -> Result<ScanStatusResponse, Error>;
+}
+
+/// An IP or netblock (in CIDR notation) to scan. Without any services specified shodan scans
+/// all the ports and protocols it knows about.
+#[derive(Debug, Clone)]
+pub struct ScanTarget {
+    target: String,
+    services: Vec<(u16, String)>,
+}
+
+impl ScanTarget {
+    pub fn new(target: impl Into<String>) -> Self {
+        Self {
+            target: target.into(),
+            services: vec![],
+        }
+    }
+
+    /// Restricts the scan of this target to the given port and protocol. The protocol has to
+    /// be one of the names returned by `get_scanning_protocols`.
+    pub fn service(mut self, port: u16, protocol: impl Into<String>) -> Self {
+        self.services.push((port, protocol.into()));
+
+        self
+    }
+}
+
+impl From<&str> for ScanTarget {
+    fn from(value: &str) -> Self {
+        Self::new(value)
+    }
+}
+
+impl From<String> for ScanTarget {
+    fn from(value: String) -> Self {
+        Self::new(value)
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ScanResponse {
+    pub id: String,
+    pub count: u32,
+    pub credits_left: u32,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ScanInternetResponse {
+    pub id: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ScansResponse {
+    pub matches: Vec<ScansResponseMatch>,
+    pub total: u32,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ScansResponseMatch {
+    pub id: String,
+    pub status: ScanStatus,
+    pub created: String,
+    pub status_check: Option<String>,
+    pub credits_left: u32,
+    pub size: u32,
+}
+
+#[cfg(feature = "chrono")]
+impl ScansResponseMatch {
+    /// When the scan was submitted.
+    pub fn created_utc(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        crate::timestamp::parse(&self.created)
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ScanStatusResponse {
+    pub id: String,
+    pub count: u32,
+    pub status: ScanStatus,
+    pub created: String,
+}
+
+#[cfg(feature = "chrono")]
+impl ScanStatusResponse {
+    /// When the scan was submitted.
+    pub fn created_utc(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        crate::timestamp::parse(&self.created)
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum ScanStatus {
+    Submitting,
+    Queue,
+    Processing,
+    Done,
+    #[serde(other)]
+    Unknown,
+}
+
+/// Serializes the targets into the `ips` form field. Shodan takes a plain comma-separated list,
+/// unless services are specified in which case it wants a JSON object of target to services.
+fn encode_targets(targets: &[ScanTarget]) -> String {
+    if targets.iter().all(|target| target.services.is_empty()) {
+        return targets
+            .iter()
+            .map(|target| target.target.as_str())
+            .collect::<Vec<_>>()
+            .join(",");
+    }
+
+    let targets = targets
+        .iter()
+        .map(|target| {
+            let services = target
+                .services
+                .iter()
+                .map(|(port, protocol)| serde_json::json!([port, protocol]))
+                .collect::<Vec<_>>();
+
+            (target.target.clone(), serde_json::Value::from(services))
+        })
+        .collect::<serde_json::Map<_, _>>();
+
+    serde_json::Value::Object(targets).to_string()
 }
 
 #[async_trait]
 impl Scanning for ShodanClient {
     async fn get_scanning_ports(&self) -> Result<ShodanClientResponse<Vec<u16>>, Error> {
-        Self::fetch(self.build_request_url("/shodan/ports", &Default::default())?).await
+        self.fetch(self.build_request_url("/shodan/ports", &Default::default())?)
+            .await
     }
 
     async fn get_scanning_protocols(
         &self,
     ) -> Result<ShodanClientResponse<HashMap<String, String>>, Error> {
-        Self::fetch(self.build_request_url("/shodan/protocols", &Default::default())?).await
+        self.fetch(self.build_request_url("/shodan/protocols", &Default::default())?)
+            .await
+    }
+
+    async fn submit_scan(&self, targets: Vec<ScanTarget>) -> Result<ScanResponse, Error> {
+        let mut parameters = ParameterBag::default();
+        parameters.set("ips", encode_targets(&targets));
+
+        self.send(
+            Method::POST,
+            self.build_request_url("/shodan/scan", &Default::default())?,
+            Some(parameters.into_form()),
+            CallKind::Metered,
+        )
+        .await
+    }
+
+    async fn scan_internet(&self, port: u16, protocol: String) -> Result<String, Error> {
+        let protocols = match self.get_scanning_protocols().await? {
+            ShodanClientResponse::Response(protocols) => protocols,
+            ShodanClientResponse::Error(e) => {
+                return Err(Error::from_api_error(StatusCode::OK, e, None))
+            }
+        };
+
+        if !protocols.contains_key(&protocol) {
+            return Err(Error::UnknownProtocol(protocol));
+        }
+
+        let mut parameters = ParameterBag::default();
+        parameters.set("port", port.to_string());
+        parameters.set("protocol", protocol);
+
+        let response: ScanInternetResponse = self
+            .send(
+                Method::POST,
+                self.build_request_url("/shodan/scan/internet", &Default::default())?,
+                Some(parameters.into_form()),
+                CallKind::Metered,
+            )
+            .await?;
+
+        Ok(response.id)
+    }
+
+    async fn get_scans(&self) -> Result<ScansResponse, Error> {
+        self.fetch(self.build_request_url("/shodan/scans", &Default::default())?)
+            .await
+    }
+
+    async fn get_scan_status(&self, id: String) -> Result<ScanStatusResponse, Error> {
+        self.fetch(
+            self.build_request_url(format!("/shodan/scan/{id}").as_str(), &Default::default())?,
+        )
+        .await
+    }
+
+    async fn wait_for_scan(
+        &self,
+        id: String,
+        interval: Duration,
+        timeout: Duration,
+    ) -> Result<ScanStatusResponse, Error> {
+        let deadline = tokio::time::Instant::now() + timeout;
+
+        loop {
+            let status = self.get_scan_status(id.clone()).await?;
+            if status.status == ScanStatus::Done {
+                return Ok(status);
+            }
+
+            // The last wait is cut short so the status gets checked once more at the deadline
+            let remaining = deadline.saturating_duration_since(tokio::time::Instant::now());
+            if remaining.is_zero() {
+                return Err(Error::ScanTimeout {
+                    id,
+                    status: status.status,
+                });
+            }
+
+            tokio::time::sleep(interval.min(remaining)).await;
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::tests::get_test_api_key;
+    use crate::testing::{MockResponse, MockServer};
+    use crate::tests::test_client;
     use crate::*;
+    use reqwest::{Method, StatusCode};
+    use std::time::Duration;
+
+    fn scan_status(status: &str) -> MockResponse {
+        MockResponse::json(
+            StatusCode::OK,
+            format!(
+                r#"{{"id": "R2XRT5HH6X67PFAB", "count": 1, "status": "{status}", "created": "2023-03-01T08:17:43.794000"}}"#
+            ),
+        )
+    }
 
     #[tokio::test]
     async fn can_get_ports() {
-        let client = ShodanClient::new(get_test_api_key());
+        let client = test_client().await;
         client.get_scanning_ports().await.unwrap();
     }
 
     #[tokio::test]
     async fn can_get_protocols() {
-        let client = ShodanClient::new(get_test_api_key());
+        let client = test_client().await;
         client.get_scanning_protocols().await.unwrap();
     }
+
+    #[tokio::test]
+    async fn can_get_scans() {
+        let client = test_client().await;
+        client.get_scans().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn can_submit_scan() {
+        let server = MockServer::with_fixtures().await;
+        let scan = server
+            .client()
+            .submit_scan(vec!["8.8.8.8".into(), "1.1.1.0/24".into()])
+            .await
+            .unwrap();
+        assert_eq!(scan.id, "R2XRT5HH6X67PFAB");
+
+        let request = &server.received_requests()[0];
+        assert_eq!(request.method, Method::POST);
+        assert_eq!(
+            String::from_utf8_lossy(&request.body),
+            "ips=8.8.8.8%2C1.1.1.0%2F24"
+        );
+    }
+
+    #[tokio::test]
+    async fn can_submit_scan_with_services() {
+        let server = MockServer::with_fixtures().await;
+        server
+            .client()
+            .submit_scan(vec![ScanTarget::new("8.8.8.8")
+                .service(53, "dns-udp")
+                .service(443, "https")])
+            .await
+            .unwrap();
+
+        let request = &server.received_requests()[0];
+        let form = url::form_urlencoded::parse(&request.body)
+            .into_owned()
+            .collect::<Vec<_>>();
+        assert_eq!(
+            form,
+            vec![(
+                String::from("ips"),
+                String::from(r#"{"8.8.8.8":[[53,"dns-udp"],[443,"https"]]}"#)
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn can_scan_internet() {
+        let server = MockServer::with_fixtures().await;
+        let id = server
+            .client()
+            .scan_internet(8443, String::from("https"))
+            .await
+            .unwrap();
+        assert_eq!(id, "9FQOUXPHE5GNXBTU");
+
+        let request = server.received_requests().pop().unwrap();
+        assert_eq!(request.path, "/shodan/scan/internet");
+        assert_eq!(
+            String::from_utf8_lossy(&request.body),
+            "port=8443&protocol=https"
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_unknown_internet_scan_protocol() {
+        let server = MockServer::with_fixtures().await;
+        let error = server
+            .client()
+            .scan_internet(8443, String::from("gopher"))
+            .await
+            .unwrap_err();
+
+        assert!(matches!(error, Error::UnknownProtocol(protocol) if protocol == "gopher"));
+        assert!(server
+            .received_requests()
+            .iter()
+            .all(|request| request.method == Method::GET));
+    }
+
+    #[tokio::test]
+    async fn can_get_scan_status() {
+        let server = MockServer::with_fixtures().await;
+        let status = server
+            .client()
+            .get_scan_status(String::from("R2XRT5HH6X67PFAB"))
+            .await
+            .unwrap();
+
+        assert_eq!(status.status, ScanStatus::Done);
+    }
+
+    #[tokio::test]
+    async fn waits_for_scan_to_finish() {
+        let server = MockServer::with_fixtures().await;
+        server.mock_once(
+            Method::GET,
+            "/shodan/scan/R2XRT5HH6X67PFAB",
+            scan_status("QUEUE"),
+        );
+        server.mock_once(
+            Method::GET,
+            "/shodan/scan/R2XRT5HH6X67PFAB",
+            scan_status("PROCESSING"),
+        );
+
+        let status = server
+            .client()
+            .wait_for_scan(
+                String::from("R2XRT5HH6X67PFAB"),
+                Duration::from_millis(10),
+                Duration::from_secs(5),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(status.status, ScanStatus::Done);
+        assert_eq!(server.received_requests().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn checks_scan_status_at_deadline() {
+        let server = MockServer::with_fixtures().await;
+        server.mock_once(
+            Method::GET,
+            "/shodan/scan/R2XRT5HH6X67PFAB",
+            scan_status("PROCESSING"),
+        );
+
+        let status = server
+            .client()
+            .wait_for_scan(
+                String::from("R2XRT5HH6X67PFAB"),
+                Duration::from_secs(60),
+                Duration::from_millis(50),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(status.status, ScanStatus::Done);
+        assert_eq!(server.received_requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn times_out_waiting_for_scan() {
+        let server = MockServer::with_fixtures().await;
+        server.mock(
+            Method::GET,
+            "/shodan/scan/R2XRT5HH6X67PFAB",
+            scan_status("PROCESSING"),
+        );
+
+        let error = server
+            .client()
+            .wait_for_scan(
+                String::from("R2XRT5HH6X67PFAB"),
+                Duration::from_millis(10),
+                Duration::from_millis(50),
+            )
+            .await
+            .unwrap_err();
+
+        assert!(matches!(
+            error,
+            Error::ScanTimeout {
+                status: ScanStatus::Processing,
+                ..
+            }
+        ));
+    }
 }
@@ -1,12 +1,23 @@
 use crate::*;
 use async_trait::async_trait;
+use futures::stream::{self, BoxStream, StreamExt};
 use serde::Deserialize;
+use std::borrow::Borrow;
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::net::{IpAddr, Ipv6Addr};
 
 #[async_trait]
 pub trait Search {
+    async fn host_ip_with(&self, request: HostIpRequest) -> Result<SearchHostIpResponse, Error>;
+
+    async fn host_search_with(&self, request: HostSearchRequest) -> Result<SearchResult, Error>;
+
+    async fn host_count_with(&self, request: HostCountRequest) -> Result<CountResponse, Error>;
+
     async fn host_ip(
         &self,
-        ip: String,
+        ip: IpAddr,
         history: Option<bool>,
         minifi: Option<bool>,
     ) -> Result<SearchHostIpResponse, Error>;
@@ -19,6 +30,16 @@ pub trait Search {
         minifi: Option<bool>,
     ) -> Result<SearchResult, Error>;
 
+    /// Walks through the pages of a search one request at a time, yielding the matches as they
+    /// come in. Stops at the end of the results, on an empty page or when one of the `limits`
+    /// is reached. Failed pages end the stream with their error, ex: `InsufficientCredits` when
+    /// the account runs out of query credits partway.
+    fn host_search_stream(
+        &self,
+        query: String,
+        limits: SearchLimits,
+    ) -> BoxStream<'static, Result<SearchResultMatch, Error>>;
+
     async fn host_count(&self, query: String, facets: Option<&str>)
         -> Result<CountResponse, Error>;
 
@@ -29,12 +50,184 @@ pub trait Search {
     async fn host_tokens(&self, query: String) -> Result<TokenResponse, Error>;
 }
 
+#[non_exhaustive]
+#[derive(Debug, Clone)]
+pub struct HostIpRequest {
+    pub ip: IpAddr,
+    pub history: Option<bool>,
+    pub minify: Option<bool>,
+}
+
+impl HostIpRequest {
+    pub fn new(ip: impl Into<IpAddr>) -> Self {
+        Self {
+            ip: ip.into(),
+            history: None,
+            minify: None,
+        }
+    }
+
+    /// Includes the banners shodan saw on the host in the past.
+    pub fn history(mut self, history: bool) -> Self {
+        self.history = Some(history);
+
+        self
+    }
+
+    /// Only returns the list of ports and the general host information, without banners.
+    pub fn minify(mut self, minify: bool) -> Self {
+        self.minify = Some(minify);
+
+        self
+    }
+}
+
+#[non_exhaustive]
+#[derive(Debug, Clone, Default)]
+pub struct HostSearchRequest {
+    pub query: String,
+    pub facets: Option<String>,
+    pub page: Option<u32>,
+    pub minify: Option<bool>,
+}
+
+impl HostSearchRequest {
+    pub fn new(query: impl Into<String>) -> Self {
+        Self {
+            query: query.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Comma-separated facets to summarize the results by, ex: `os,country:5`.
+    pub fn facets(mut self, facets: impl Into<String>) -> Self {
+        self.facets = Some(facets.into());
+
+        self
+    }
+
+    /// Pages past the first one cost a query credit each.
+    pub fn page(mut self, page: u32) -> Self {
+        self.page = Some(page);
+
+        self
+    }
+
+    /// Truncates larger fields of the banners, defaults to `true` on shodan's end.
+    pub fn minify(mut self, minify: bool) -> Self {
+        self.minify = Some(minify);
+
+        self
+    }
+}
+
+#[non_exhaustive]
+#[derive(Debug, Clone, Default)]
+pub struct HostCountRequest {
+    pub query: String,
+    pub facets: Option<String>,
+}
+
+impl HostCountRequest {
+    pub fn new(query: impl Into<String>) -> Self {
+        Self {
+            query: query.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn facets(mut self, facets: impl Into<String>) -> Self {
+        self.facets = Some(facets.into());
+
+        self
+    }
+}
+
+/// Caps on how far `host_search_stream` goes, unlimited by default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SearchLimits {
+    max_results: Option<u32>,
+    max_credits: Option<u32>,
+}
+
+impl SearchLimits {
+    pub fn max_results(mut self, max_results: u32) -> Self {
+        self.max_results = Some(max_results);
+
+        self
+    }
+
+    /// Every page is counted as a query credit, even though shodan doesn't charge for some.
+    pub fn max_credits(mut self, max_credits: u32) -> Self {
+        self.max_credits = Some(max_credits);
+
+        self
+    }
+}
+
+struct SearchPages {
+    client: ShodanClient,
+    query: String,
+    limits: SearchLimits,
+    matches: VecDeque<SearchResultMatch>,
+    /// Next page to fetch, shodan counts them from 1.
+    page: u32,
+    yielded: u32,
+    exhausted: bool,
+}
+
+impl SearchPages {
+    async fn next_match(&mut self) -> Option<Result<SearchResultMatch, Error>> {
+        loop {
+            if self
+                .limits
+                .max_results
+                .is_some_and(|max| self.yielded >= max)
+            {
+                return None;
+            }
+
+            if let Some(result) = self.matches.pop_front() {
+                self.yielded += 1;
+                return Some(Ok(result));
+            }
+
+            let pages_fetched = self.page - 1;
+            if self.exhausted
+                || self
+                    .limits
+                    .max_credits
+                    .is_some_and(|max| pages_fetched >= max)
+            {
+                return None;
+            }
+
+            let result = self
+                .client
+                .host_search_with(HostSearchRequest::new(self.query.clone()).page(self.page))
+                .await;
+            let result = match result {
+                Ok(result) => result,
+                Err(e) => {
+                    self.exhausted = true;
+                    return Some(Err(e));
+                }
+            };
+
+            self.page += 1;
+            self.exhausted = result.matches.is_empty()
+                || self.yielded as i64 + result.matches.len() as i64 >= result.total;
+            self.matches.extend(result.matches);
+        }
+    }
+}
+
 #[derive(Deserialize, Debug)]
 pub struct SearchHostIpResponse {
     pub last_update: String,
 
-    pub ip: u32,
-    pub ip_str: String,
+    #[serde(flatten, deserialize_with = "deserialize_ip")]
+    pub ip: IpAddr,
     pub ports: Vec<u16>,
     pub isp: Option<String>,
     pub asn: Option<String>,
@@ -54,12 +247,55 @@ pub struct SearchHostIpResponse {
     pub dma_code: Option<u32>,
     pub tags: Vec<String>,
     pub area_code: Option<String>,
+    #[serde(default)]
+    pub vulns: Vec<String>,
+    /// The service banners of the host. When requested with `history` this includes every
+    /// banner Shodan has collected for it over time, not just the most recent ones.
+    #[serde(default)]
+    pub data: Vec<Banner>,
+}
+
+#[cfg(feature = "chrono")]
+impl SearchHostIpResponse {
+    /// When any of the host's banners was last updated.
+    pub fn last_update_utc(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        crate::timestamp::parse(&self.last_update)
+    }
+}
+
+impl SearchHostIpResponse {
+    /// Groups the banners by service, ex: `53/udp` and `53/tcp` separately, newest first.
+    pub fn banners_by_service(&self) -> BTreeMap<ServiceKey, Vec<&Banner>> {
+        let mut services = BTreeMap::<_, Vec<_>>::new();
+        for banner in &self.data {
+            let key = ServiceKey {
+                port: banner.port,
+                transport: banner.transport.clone(),
+            };
+            services.entry(key).or_default().push(banner);
+        }
+
+        for banners in services.values_mut() {
+            sort_by_timestamp(banners);
+            banners.reverse();
+        }
+
+        services
+    }
+
+    /// The most recent banner of every service, in port order.
+    pub fn latest_banners(&self) -> Vec<&Banner> {
+        self.banners_by_service()
+            .into_values()
+            .filter_map(|banners| banners.into_iter().next())
+            .collect()
+    }
 }
 
 #[derive(Deserialize, Debug)]
 pub struct CountResponse {
     pub total: u32,
-    pub facets: Option<HashMap<String, Vec<Facet>>>,
+    pub facets: Option<HashMap<FacetName, Vec<Facet>>>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -73,14 +309,145 @@ pub struct TokenResponse {
 #[derive(Deserialize, Debug)]
 pub struct Facet {
     pub count: u32,
+    /// Numeric values, like those of the `port` facet, are turned into strings as well.
+    #[serde(deserialize_with = "deserialize_facet_value")]
     pub value: String,
 }
 
+/// Reads the address of a host or banner. Shodan spreads it over `ip_str`, `ipv6` and `ip`, the
+/// latter holding IPv4 addresses as a number.
+fn deserialize_ip<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<IpAddr, D::Error> {
+    #[derive(Deserialize)]
+    struct Address {
+        ip: Option<u32>,
+        ip_str: Option<IpAddr>,
+        ipv6: Option<Ipv6Addr>,
+    }
+
+    let address = Address::deserialize(deserializer)?;
+    address
+        .ip_str
+        .or(address.ipv6.map(IpAddr::V6))
+        .or(address.ip.map(|ip| IpAddr::V4(ip.into())))
+        .ok_or_else(|| serde::de::Error::missing_field("ip_str"))
+}
+
+fn deserialize_facet_value<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<String, D::Error> {
+    match serde_json::Value::deserialize(deserializer)? {
+        serde_json::Value::String(value) => Ok(value),
+        value => Ok(value.to_string()),
+    }
+}
+
+macro_rules! facet_names {
+    ($($variant:ident => $name:literal,)*) => {
+        /// Properties search results can be summarized by. `Other` covers facets this client
+        /// doesn't know about yet.
+        #[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize)]
+        #[serde(from = "String")]
+        pub enum FacetName {
+            $($variant,)*
+            Other(String),
+        }
+
+        impl FacetName {
+            pub fn as_str(&self) -> &str {
+                match self {
+                    $(FacetName::$variant => $name,)*
+                    FacetName::Other(name) => name.as_str(),
+                }
+            }
+        }
+
+        impl From<String> for FacetName {
+            fn from(name: String) -> Self {
+                match name.as_str() {
+                    $($name => FacetName::$variant,)*
+                    _ => FacetName::Other(name),
+                }
+            }
+        }
+    };
+}
+
+facet_names! {
+    Asn => "asn",
+    City => "city",
+    CloudProvider => "cloud.provider",
+    CloudRegion => "cloud.region",
+    CloudService => "cloud.service",
+    Country => "country",
+    Cpe => "cpe",
+    Device => "device",
+    Domain => "domain",
+    HasScreenshot => "has_screenshot",
+    Hash => "hash",
+    HttpComponent => "http.component",
+    HttpComponentCategory => "http.component_category",
+    HttpFaviconHash => "http.favicon.hash",
+    HttpHtmlHash => "http.html_hash",
+    HttpStatus => "http.status",
+    HttpTitle => "http.title",
+    HttpWaf => "http.waf",
+    Ip => "ip",
+    Isp => "isp",
+    Link => "link",
+    Org => "org",
+    Os => "os",
+    Port => "port",
+    Postal => "postal",
+    Product => "product",
+    Region => "region",
+    ScreenshotLabel => "screenshot.label",
+    SshCipher => "ssh.cipher",
+    SshFingerprint => "ssh.fingerprint",
+    SshHassh => "ssh.hassh",
+    SshType => "ssh.type",
+    SslAlpn => "ssl.alpn",
+    SslCertAlg => "ssl.cert.alg",
+    SslCertExpired => "ssl.cert.expired",
+    SslCertIssuerCn => "ssl.cert.issuer.cn",
+    SslCertPubkeyBits => "ssl.cert.pubkey.bits",
+    SslCertSubjectCn => "ssl.cert.subject.cn",
+    SslCipherName => "ssl.cipher.name",
+    SslJa3s => "ssl.ja3s",
+    SslJarm => "ssl.jarm",
+    SslVersion => "ssl.version",
+    State => "state",
+    Tag => "tag",
+    Uptime => "uptime",
+    Version => "version",
+    Vuln => "vuln",
+    VulnVerified => "vuln.verified",
+}
+
+impl From<&str> for FacetName {
+    fn from(name: &str) -> Self {
+        Self::from(name.to_string())
+    }
+}
+
+impl fmt::Display for FacetName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct SearchResult {
     pub matches: Vec<SearchResultMatch>,
     pub total: i64,
-    pub facets: Option<HashMap<String, Vec<Facet>>>,
+    pub facets: Option<HashMap<FacetName, Vec<Facet>>>,
+}
+
+/// A single service shodan found on a host, as returned by searches and the streaming API.
+pub type Banner = SearchResultMatch;
+
+/// Sorts banners oldest first, the ones with a timestamp that can't be read go in front.
+pub(crate) fn sort_by_timestamp<B: Borrow<Banner>>(banners: &mut [B]) {
+    banners.sort_by_cached_key(|banner| timestamp::seconds(&banner.borrow().timestamp));
 }
 
 #[derive(Debug, Deserialize)]
@@ -99,12 +466,12 @@ pub struct SearchResultMatch {
     pub cloud: Option<Cloud>,
     pub hostnames: Vec<String>,
     pub location: LocationClass,
-    pub ip: Option<i64>,
+    #[serde(flatten, deserialize_with = "deserialize_ip")]
+    pub ip: IpAddr,
     pub domains: Vec<String>,
     pub org: Option<String>,
     pub data: String,
     pub port: i64,
-    pub ip_str: String,
     pub product: Option<String>,
     pub cpe23: Option<Vec<String>>,
     pub cpe: Option<Vec<String>>,
@@ -112,7 +479,14 @@ pub struct SearchResultMatch {
     pub mysql: Option<Mysql>,
     pub info: Option<String>,
     pub vulns: Option<HashMap<String, Vuln>>,
-    pub ipv6: Option<String>,
+}
+
+#[cfg(feature = "chrono")]
+impl SearchResultMatch {
+    /// When shodan collected the banner.
+    pub fn timestamp_utc(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        crate::timestamp::parse(&self.timestamp)
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -132,10 +506,10 @@ pub struct Http {
     pub sitemap_hash: Option<i64>,
     pub robots: Option<String>,
     pub server: Option<String>,
-    pub headers_hash: i64,
+    pub headers_hash: Option<i64>,
     pub host: String,
-    pub html: String,
-    pub location: String,
+    pub html: Option<String>,
+    pub location: Option<String>,
     pub components: Option<HashMap<String, Component>>,
     pub html_hash: i64,
     pub sitemap: Option<String>,
@@ -166,8 +540,8 @@ pub struct Redirect {
 
 #[derive(Debug, Deserialize)]
 pub struct LocationClass {
-    pub city: String,
-    pub region_code: String,
+    pub city: Option<String>,
+    pub region_code: Option<String>,
     pub area_code: Option<String>,
     pub longitude: f64,
     pub latitude: f64,
@@ -189,31 +563,41 @@ pub struct Mysql {
 
 #[derive(Debug, Deserialize)]
 pub struct Shodan {
-    pub region: String,
+    pub region: Option<String>,
     pub ptr: Option<bool>,
     pub module: String,
     pub id: String,
     pub options: HashMap<String, String>,
     pub crawler: String,
+    /// The network alert that matched the banner, only set on the alert streams.
+    pub alert: Option<BannerAlert>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct BannerAlert {
+    pub id: String,
+    pub name: String,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct Ssl {
     pub chain_sha256: Vec<String>,
-    pub jarm: String,
+    /// Missing on banners collected before shodan started computing JARM fingerprints.
+    pub jarm: Option<String>,
     pub chain: Vec<String>,
     pub dhparams: Option<Dhparams>,
     pub versions: Vec<String>,
     //pub acceptable_cas: Vec<String>,
     pub tlsext: Vec<Tlsext>,
     #[serde(rename = "ja3s")]
-    pub ja3_s: String,
+    pub ja3_s: Option<String>,
     pub cert: Cert,
     pub cipher: Cipher,
     pub trust: Trust,
+    #[serde(default)]
     pub handshake_states: Vec<String>,
     pub alpn: Vec<String>,
-    pub ocsp: Ocsp,
+    pub ocsp: Option<Ocsp>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -231,6 +615,19 @@ pub struct Cert {
     pub issuer: Issuer,
 }
 
+#[cfg(feature = "chrono")]
+impl Cert {
+    /// Start of the certificate's validity.
+    pub fn issued_utc(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        crate::timestamp::parse(&self.issued)
+    }
+
+    /// End of the certificate's validity.
+    pub fn expires_utc(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        crate::timestamp::parse(&self.expires)
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct Extension {
     pub critical: Option<bool>,
@@ -298,6 +695,30 @@ pub struct Ocsp {
     pub certificate_id: Option<CertificateId>,
 }
 
+#[cfg(feature = "chrono")]
+impl Ocsp {
+    /// When the OCSP responder signed the response.
+    pub fn produced_at_utc(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        self.produced_at
+            .as_deref()
+            .and_then(crate::timestamp::parse)
+    }
+
+    /// When the responder will have newer revocation status.
+    pub fn next_update_utc(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        self.next_update
+            .as_deref()
+            .and_then(crate::timestamp::parse)
+    }
+
+    /// When the revocation status was last known to be correct.
+    pub fn this_update_utc(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        self.this_update
+            .as_deref()
+            .and_then(crate::timestamp::parse)
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct CertificateId {
     pub hash_algorithm: String,
@@ -335,18 +756,51 @@ pub struct Vuln {
 
 #[async_trait]
 impl Search for ShodanClient {
+    async fn host_ip_with(&self, request: HostIpRequest) -> Result<SearchHostIpResponse, Error> {
+        let mut parameters = ParameterBag::default();
+        parameters.set_optional("history", request.history);
+        parameters.set_optional("minify", request.minify);
+
+        self.fetch(
+            self.build_request_url(format!("/shodan/host/{}", request.ip).as_str(), &parameters)?,
+        )
+        .await
+    }
+
+    async fn host_search_with(&self, request: HostSearchRequest) -> Result<SearchResult, Error> {
+        self.validate_query(&request.query).await?;
+
+        let mut parameters = ParameterBag::default();
+        parameters.set("query", request.query);
+        parameters.set_optional("facets", request.facets);
+        parameters.set_optional("page", request.page);
+        parameters.set_optional("minify", request.minify);
+
+        self.fetch_metered(self.build_request_url("/shodan/host/search", &parameters)?)
+            .await
+    }
+
+    async fn host_count_with(&self, request: HostCountRequest) -> Result<CountResponse, Error> {
+        let mut parameters = ParameterBag::default();
+        parameters.set("query", request.query);
+        parameters.set_optional("facets", request.facets);
+
+        self.fetch(self.build_request_url("/shodan/host/count", &parameters)?)
+            .await
+    }
+
     async fn host_ip(
         &self,
-        ip: String,
+        ip: IpAddr,
         history: Option<bool>,
         minifi: Option<bool>,
     ) -> Result<SearchHostIpResponse, Error> {
-        let mut parameters = ParameterBag::default();
-        parameters.set_optional("history", history);
-        parameters.set_optional("minifi", minifi);
-
-        Self::fetch(self.build_request_url(format!("/shodan/host/{ip}").as_str(), &parameters)?)
-            .await
+        self.host_ip_with(HostIpRequest {
+            history,
+            minify: minifi,
+            ..HostIpRequest::new(ip)
+        })
+        .await
     }
 
     async fn host_search(
@@ -356,13 +810,34 @@ impl Search for ShodanClient {
         page: Option<u32>,
         minifi: Option<bool>,
     ) -> Result<SearchResult, Error> {
-        let mut parameters = ParameterBag::default();
-        parameters.set("query", query);
-        parameters.set_optional("facets", facets);
-        parameters.set_optional("page", page);
-        parameters.set_optional("minifi", minifi);
+        self.host_search_with(HostSearchRequest {
+            facets: facets.map(String::from),
+            page,
+            minify: minifi,
+            ..HostSearchRequest::new(query)
+        })
+        .await
+    }
+
+    fn host_search_stream(
+        &self,
+        query: String,
+        limits: SearchLimits,
+    ) -> BoxStream<'static, Result<SearchResultMatch, Error>> {
+        let pages = SearchPages {
+            client: self.clone(),
+            query,
+            limits,
+            matches: VecDeque::new(),
+            page: 1,
+            yielded: 0,
+            exhausted: false,
+        };
 
-        Self::fetch(self.build_request_url("/shodan/host/search", &parameters)?).await
+        stream::unfold(pages, |mut pages| async move {
+            pages.next_match().await.map(|result| (result, pages))
+        })
+        .boxed()
     }
 
     async fn host_count(
@@ -370,20 +845,20 @@ impl Search for ShodanClient {
         query: String,
         facets: Option<&str>,
     ) -> Result<CountResponse, Error> {
-        let mut parameters = ParameterBag::default();
-        parameters.set("query", query);
-        parameters.set_optional("facets", facets);
-
-        Self::fetch(self.build_request_url("/shodan/host/count", &parameters)?).await
+        self.host_count_with(HostCountRequest {
+            facets: facets.map(String::from),
+            ..HostCountRequest::new(query)
+        })
+        .await
     }
 
     async fn host_facets(&self) -> Result<Vec<String>, Error> {
-        Self::fetch(self.build_request_url("/shodan/host/search/facets", &Default::default())?)
+        self.fetch(self.build_request_url("/shodan/host/search/facets", &Default::default())?)
             .await
     }
 
     async fn host_filters(&self) -> Result<Vec<String>, Error> {
-        Self::fetch(self.build_request_url("/shodan/host/search/filters", &Default::default())?)
+        self.fetch(self.build_request_url("/shodan/host/search/filters", &Default::default())?)
             .await
     }
 
@@ -391,39 +866,123 @@ impl Search for ShodanClient {
         let mut parameters = ParameterBag::default();
         parameters.set("query", query);
 
-        Self::fetch(self.build_request_url("/shodan/host/search/tokens", &parameters)?).await
+        self.fetch(self.build_request_url("/shodan/host/search/tokens", &parameters)?)
+            .await
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::tests::get_test_api_key;
+    use crate::testing::{MockResponse, MockServer};
+    use crate::tests::{banner, banner_json, test_client};
     use crate::*;
+    use futures::StreamExt;
+    use reqwest::{Method, StatusCode};
+    use std::net::IpAddr;
+
+    /// A page of search results made out of the matches in the search fixture.
+    fn search_page(matches: &[usize], total: u32) -> MockResponse {
+        let matches = matches
+            .iter()
+            .map(|index| banner_json(*index, serde_json::json!({})))
+            .collect::<Vec<_>>();
+
+        MockResponse::json(
+            StatusCode::OK,
+            serde_json::json!({ "matches": matches, "total": total }).to_string(),
+        )
+    }
 
     #[tokio::test]
     async fn can_get_google_host_ip() {
-        let client = ShodanClient::new(get_test_api_key());
+        let client = test_client().await;
         client
-            .host_ip(String::from("8.8.8.8"), None, None)
+            .host_ip("8.8.8.8".parse().unwrap(), None, None)
             .await
             .unwrap();
     }
 
+    #[tokio::test]
+    async fn groups_host_banners_by_service() {
+        let mut fixture = serde_json::from_str::<serde_json::Value>(include_str!(
+            "../fixtures/shodan_host_8.8.8.8.json"
+        ))
+        .unwrap();
+        let mut historical = fixture["data"][0].clone();
+        historical["timestamp"] = "2022-11-05T03:21:09.000000".into();
+        historical["data"] = "\nRecursion: disabled".into();
+        let mut tcp = fixture["data"][0].clone();
+        tcp["transport"] = "tcp".into();
+        tcp["data"] = "\nRecursion: tcp".into();
+        fixture["data"].as_array_mut().unwrap().push(historical);
+        fixture["data"].as_array_mut().unwrap().push(tcp);
+        fixture["vulns"] = serde_json::json!(["CVE-2023-0001"]);
+
+        let server = MockServer::with_fixtures().await;
+        server.mock_once(
+            Method::GET,
+            "/shodan/host/8.8.8.8",
+            MockResponse::json(StatusCode::OK, fixture.to_string()),
+        );
+
+        let host = server
+            .client()
+            .host_ip_with(HostIpRequest::new([8, 8, 8, 8]).history(true))
+            .await
+            .unwrap();
+        assert_eq!(host.vulns, vec!["CVE-2023-0001"]);
+        assert_eq!(host.data.len(), 4);
+
+        let services = host.banners_by_service();
+        assert_eq!(
+            services.keys().map(ToString::to_string).collect::<Vec<_>>(),
+            vec!["53/tcp", "53/udp", "443/tcp"]
+        );
+        let udp = ServiceKey {
+            port: 53,
+            transport: String::from("udp"),
+        };
+        assert_eq!(
+            services[&udp]
+                .iter()
+                .map(|banner| banner.data.as_str())
+                .collect::<Vec<_>>(),
+            vec!["\nRecursion: enabled", "\nRecursion: disabled"]
+        );
+
+        let latest = host.latest_banners();
+        assert_eq!(latest.len(), 3);
+        assert_eq!(latest[1].data, "\nRecursion: enabled");
+    }
+
+    #[test]
+    fn reads_banner_addresses() {
+        let ipv4 = banner(0, serde_json::json!({}));
+        assert_eq!(ipv4.ip, "142.250.64.78".parse::<IpAddr>().unwrap());
+
+        // IPv6 banners come without the numeric `ip`
+        let ipv6 = banner(
+            0,
+            serde_json::json!({"ip": null, "ip_str": null, "ipv6": "2001:4860:4860::8888"}),
+        );
+        assert_eq!(ipv6.ip, "2001:4860:4860::8888".parse::<IpAddr>().unwrap());
+    }
+
     #[tokio::test]
     async fn can_get_host_facets() {
-        let client = ShodanClient::new(get_test_api_key());
+        let client = test_client().await;
         client.host_facets().await.unwrap();
     }
 
     #[tokio::test]
     async fn can_get_host_filters() {
-        let client = ShodanClient::new(get_test_api_key());
+        let client = test_client().await;
         client.host_filters().await.unwrap();
     }
 
     #[tokio::test]
     async fn can_get_google_count() {
-        let client = ShodanClient::new(get_test_api_key());
+        let client = test_client().await;
         client
             .host_count(String::from("google"), None)
             .await
@@ -432,16 +991,49 @@ mod tests {
 
     #[tokio::test]
     async fn can_get_google_count_with_facets() {
-        let client = ShodanClient::new(get_test_api_key());
+        let client = test_client().await;
         client
             .host_count(String::from("google"), Some("os,country"))
             .await
             .unwrap();
     }
 
+    #[tokio::test]
+    async fn decodes_sparse_host_history() {
+        let server = MockServer::with_fixtures().await;
+        let host = server
+            .client()
+            .host_ip_with(HostIpRequest::new([198, 51, 100, 7]).history(true))
+            .await
+            .unwrap();
+
+        // Country level geolocation and a TLS banner from before JARM
+        assert_eq!(host.data.len(), 2);
+        assert!(host
+            .data
+            .iter()
+            .all(|banner| banner.location.city.is_none()));
+        let ssl = host.data[1].ssl.as_ref().unwrap();
+        assert!(ssl.jarm.is_none() && ssl.ocsp.is_none());
+    }
+
+    #[tokio::test]
+    async fn can_get_host_ip_with_request() {
+        let server = MockServer::with_fixtures().await;
+        server
+            .client()
+            .host_ip_with(HostIpRequest::new([8, 8, 8, 8]).history(true).minify(true))
+            .await
+            .unwrap();
+
+        let request = &server.received_requests()[0];
+        assert_eq!(request.query_param("history"), Some("true"));
+        assert_eq!(request.query_param("minify"), Some("true"));
+    }
+
     #[tokio::test]
     async fn can_get_google_search() {
-        let client = ShodanClient::new(get_test_api_key());
+        let client = test_client().await;
         client
             .host_search(String::from("google"), None, None, Some(true))
             .await
@@ -450,10 +1042,145 @@ mod tests {
 
     #[tokio::test]
     async fn can_get_raspbian_tokens() {
-        let client = ShodanClient::new(get_test_api_key());
+        let client = test_client().await;
         client
             .host_tokens(String::from("Raspbian port:22"))
             .await
             .unwrap();
     }
+
+    #[tokio::test]
+    async fn can_stream_search_pages() {
+        let server = MockServer::with_fixtures().await;
+        server.mock_once(Method::GET, "/shodan/host/search", search_page(&[0, 1], 3));
+        server.mock_once(Method::GET, "/shodan/host/search", search_page(&[1], 3));
+
+        let ips = server
+            .client()
+            .host_search_stream(String::from("google"), SearchLimits::default())
+            .map(|result| result.unwrap().ip.to_string())
+            .collect::<Vec<_>>()
+            .await;
+
+        assert_eq!(ips, vec!["142.250.64.78", "8.8.8.8", "8.8.8.8"]);
+        let pages = server
+            .received_requests()
+            .iter()
+            .map(|request| request.query_param("page").unwrap().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(pages, vec!["1", "2"]);
+    }
+
+    #[tokio::test]
+    async fn search_stream_respects_limits() {
+        let server = MockServer::with_fixtures().await;
+        server.mock(
+            Method::GET,
+            "/shodan/host/search",
+            search_page(&[0, 1], 1000),
+        );
+
+        let results = server
+            .client()
+            .host_search_stream(
+                String::from("google"),
+                SearchLimits::default().max_results(3),
+            )
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(results.len(), 3);
+        assert_eq!(server.received_requests().len(), 2);
+
+        let results = server
+            .client()
+            .host_search_stream(
+                String::from("google"),
+                SearchLimits::default().max_credits(1),
+            )
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(results.len(), 2);
+        assert_eq!(server.received_requests().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn search_stream_reports_running_out_of_credits() {
+        let server = MockServer::with_fixtures().await;
+        server.mock_once(
+            Method::GET,
+            "/shodan/host/search",
+            search_page(&[0, 1], 1000),
+        );
+        server.mock(
+            Method::GET,
+            "/shodan/host/search",
+            MockResponse::error(
+                StatusCode::PAYMENT_REQUIRED,
+                "Insufficient query credits, please upgrade your API plan or wait for the monthly limit to reset",
+            ),
+        );
+
+        let results = server
+            .client()
+            .host_search_stream(String::from("google"), SearchLimits::default())
+            .collect::<Vec<_>>()
+            .await;
+
+        assert_eq!(results.len(), 3);
+        assert!(results[..2].iter().all(Result::is_ok));
+        assert!(matches!(results[2], Err(Error::InsufficientCredits(_))));
+    }
+
+    #[tokio::test]
+    async fn can_search_with_request() {
+        let server = MockServer::with_fixtures().await;
+        server
+            .client()
+            .host_search_with(
+                HostSearchRequest::new("google")
+                    .facets("os,country:5")
+                    .page(2)
+                    .minify(false),
+            )
+            .await
+            .unwrap();
+
+        let request = &server.received_requests()[0];
+        assert_eq!(request.query_param("query"), Some("google"));
+        assert_eq!(request.query_param("facets"), Some("os,country:5"));
+        assert_eq!(request.query_param("page"), Some("2"));
+        assert_eq!(request.query_param("minify"), Some("false"));
+    }
+
+    #[tokio::test]
+    async fn can_look_up_facets_by_name() {
+        let server = MockServer::with_fixtures().await;
+        let facets = FacetsBuilder::default()
+            .facet(FacetName::Os)
+            .facet_with_size(FacetName::Country, 2)
+            .validate(&server.client().host_facets().await.unwrap())
+            .unwrap()
+            .build();
+
+        let count = server
+            .client()
+            .host_count_with(HostCountRequest::new("google").facets(facets))
+            .await
+            .unwrap();
+        let facets = count.facets.unwrap();
+
+        assert_eq!(facets[&FacetName::Os][0].value, "Linux");
+        assert_eq!(facets[&FacetName::Country][1].value, "DE");
+        assert_eq!(
+            server.received_requests()[1].query_param("facets"),
+            Some("os,country:2")
+        );
+    }
+
+    #[test]
+    fn stringifies_numeric_facet_values() {
+        let facet = serde_json::from_str::<Facet>(r#"{"count": 12, "value": 443}"#).unwrap();
+
+        assert_eq!(facet.value, "443");
+    }
 }
@@ -0,0 +1,612 @@
+use crate::*;
+use futures::ready;
+use futures::stream::{self, BoxStream, Stream, StreamExt};
+use serde::de::Error as _;
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Real-time banners from stream.shodan.io. Streams connect when first polled and close their
+/// connection when dropped.
+pub trait Streaming {
+    fn stream(&self, filter: StreamFilter) -> BannerStream;
+
+    /// Everything shodan collects, only available on enterprise plans.
+    fn stream_banners(&self) -> BannerStream;
+
+    fn stream_asn(&self, asn: Vec<String>) -> BannerStream;
+
+    /// Banners for the given two-letter country codes.
+    fn stream_countries(&self, countries: Vec<String>) -> BannerStream;
+
+    fn stream_ports(&self, ports: Vec<u16>) -> BannerStream;
+
+    fn stream_tags(&self, tags: Vec<String>) -> BannerStream;
+
+    fn stream_vulns(&self, vulns: Vec<String>) -> BannerStream;
+
+    /// Banners matching a search-like query, ex: `port:8080 country:NL`.
+    fn stream_custom(&self, query: String) -> BannerStream;
+
+    /// Banners matching any of the network alerts on the account.
+    fn stream_alerts(&self) -> AlertStream;
+
+    fn stream_alert(&self, id: String) -> AlertStream;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StreamFilter {
+    Banners,
+    Asn(Vec<String>),
+    Countries(Vec<String>),
+    Ports(Vec<u16>),
+    Tags(Vec<String>),
+    Vulns(Vec<String>),
+    Custom(String),
+    Alerts,
+    Alert(String),
+}
+
+impl StreamFilter {
+    fn endpoint(&self) -> (String, ParameterBag) {
+        let mut parameters = ParameterBag::default();
+        let endpoint = match self {
+            StreamFilter::Banners => String::from("/shodan/banners"),
+            StreamFilter::Asn(asn) => format!("/shodan/asn/{}", asn.join(",")),
+            StreamFilter::Countries(countries) => {
+                format!("/shodan/countries/{}", countries.join(","))
+            }
+            StreamFilter::Ports(ports) => format!(
+                "/shodan/ports/{}",
+                ports
+                    .iter()
+                    .map(u16::to_string)
+                    .collect::<Vec<_>>()
+                    .join(",")
+            ),
+            StreamFilter::Tags(tags) => format!("/shodan/tags/{}", tags.join(",")),
+            StreamFilter::Vulns(vulns) => format!("/shodan/vulns/{}", vulns.join(",")),
+            StreamFilter::Custom(query) => {
+                parameters.set("query", query);
+                String::from("/shodan/custom")
+            }
+            StreamFilter::Alerts => String::from("/shodan/alert"),
+            StreamFilter::Alert(id) => format!("/shodan/alert/{id}"),
+        };
+
+        (endpoint, parameters)
+    }
+}
+
+/// Stream of banners that reconnects according to its retry policy when the connection drops,
+/// times out or the server errors. Consecutive failures count as attempts, any data coming in
+/// resets the count. Errors that aren't retried end the stream. The client's retry policy only
+/// applies to REST calls, streams reconnect indefinitely unless given a policy of their own.
+pub struct BannerStream {
+    connection: Option<StreamConnection>,
+    banners: Option<BoxStream<'static, Result<Banner, Error>>>,
+}
+
+impl BannerStream {
+    fn new(client: &ShodanClient, url: Result<String, Error>) -> Self {
+        let connection = url.map(|url| StreamConnection::new(client, url));
+
+        match connection {
+            Ok(connection) => Self {
+                connection: Some(connection),
+                banners: None,
+            },
+            Err(e) => Self {
+                connection: None,
+                banners: Some(stream::once(async { Err(e) }).boxed()),
+            },
+        }
+    }
+
+    /// Defaults to reconnecting indefinitely, backing off up to 30 seconds between attempts.
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        if let Some(connection) = self.connection.as_mut() {
+            connection.retry_policy = retry_policy;
+        }
+
+        self
+    }
+
+    /// Reconnects when nothing, not even a heartbeat, arrives for this long. Also bounds how
+    /// long connecting may take. Defaults to 90 seconds.
+    pub fn heartbeat_timeout(mut self, timeout: Duration) -> Self {
+        if let Some(connection) = self.connection.as_mut() {
+            connection.heartbeat_timeout = timeout;
+        }
+
+        self
+    }
+}
+
+impl Stream for BannerStream {
+    type Item = Result<Banner, Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let banners = this.banners.get_or_insert_with(|| {
+            let connection = this
+                .connection
+                .take()
+                .expect("Streams without a banner stream have a connection");
+
+            stream::unfold(connection, |mut connection| async move {
+                let line = connection.next_line().await?;
+                let banner = line.and_then(|line| Ok(serde_json::from_slice(&line)?));
+
+                Some((banner, connection))
+            })
+            .boxed()
+        });
+
+        banners.poll_next_unpin(cx)
+    }
+}
+
+/// A banner along with the network alert it fired for.
+#[derive(Debug)]
+pub struct AlertEvent {
+    pub alert_id: String,
+    pub alert_name: String,
+    pub banner: Banner,
+}
+
+/// Amount of banners an `AlertStream` remembers to filter out repeats after a reconnect.
+const SEEN_BANNERS: usize = 1024;
+
+/// Stream of alert events on top of a `BannerStream`. Shodan may send banners again after a
+/// reconnect, those are skipped as long as they're among the last banners seen.
+pub struct AlertStream {
+    banners: BannerStream,
+    seen: VecDeque<(String, String)>,
+}
+
+impl AlertStream {
+    fn new(banners: BannerStream) -> Self {
+        Self {
+            banners,
+            seen: VecDeque::with_capacity(SEEN_BANNERS),
+        }
+    }
+
+    /// See `BannerStream::retry_policy`.
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.banners = self.banners.retry_policy(retry_policy);
+
+        self
+    }
+
+    /// See `BannerStream::heartbeat_timeout`.
+    pub fn heartbeat_timeout(mut self, timeout: Duration) -> Self {
+        self.banners = self.banners.heartbeat_timeout(timeout);
+
+        self
+    }
+
+    fn is_repeat(&mut self, event: &AlertEvent) -> bool {
+        let key = (event.alert_id.clone(), event.banner.shodan.id.clone());
+        if self.seen.contains(&key) {
+            return true;
+        }
+
+        if self.seen.len() == SEEN_BANNERS {
+            self.seen.pop_front();
+        }
+        self.seen.push_back(key);
+
+        false
+    }
+}
+
+impl Stream for AlertStream {
+    type Item = Result<AlertEvent, Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        loop {
+            let banner = match ready!(this.banners.poll_next_unpin(cx)) {
+                Some(Ok(banner)) => banner,
+                Some(Err(e)) => return Poll::Ready(Some(Err(e))),
+                None => return Poll::Ready(None),
+            };
+
+            let Some(alert) = banner.shodan.alert.clone() else {
+                let error = serde_json::Error::missing_field("alert");
+                return Poll::Ready(Some(Err(error.into())));
+            };
+
+            let event = AlertEvent {
+                alert_id: alert.id,
+                alert_name: alert.name,
+                banner,
+            };
+            if !this.is_repeat(&event) {
+                return Poll::Ready(Some(Ok(event)));
+            }
+        }
+    }
+}
+
+/// Line-delimited connection to the streaming API that handles heartbeats and reconnects.
+struct StreamConnection {
+    http_client: reqwest::Client,
+    url: String,
+    retry_policy: RetryPolicy,
+    heartbeat_timeout: Duration,
+    response: Option<reqwest::Response>,
+    buffer: Vec<u8>,
+    failures: u32,
+    done: bool,
+}
+
+impl StreamConnection {
+    fn new(client: &ShodanClient, url: String) -> Self {
+        Self {
+            http_client: client.http_client.clone(),
+            url,
+            retry_policy: RetryPolicy::default().max_attempts(u32::MAX),
+            heartbeat_timeout: Duration::from_secs(90),
+            response: None,
+            buffer: vec![],
+            failures: 0,
+            done: false,
+        }
+    }
+
+    async fn next_line(&mut self) -> Option<Result<Vec<u8>, Error>> {
+        loop {
+            if self.done {
+                return None;
+            }
+
+            if let Some(end) = self.buffer.iter().position(|byte| *byte == b'\n') {
+                let line = self.buffer.drain(..=end).collect::<Vec<_>>();
+
+                // Shodan sends empty lines as heartbeats
+                if line.iter().all(u8::is_ascii_whitespace) {
+                    continue;
+                }
+
+                return Some(Ok(line));
+            }
+
+            let error = match self.response.as_mut() {
+                None => match tokio::time::timeout(self.heartbeat_timeout, self.connect()).await {
+                    Ok(Ok(response)) => {
+                        self.response = Some(response);
+                        continue;
+                    }
+                    Ok(Err(e)) => e,
+                    Err(_) => Error::StreamTimeout(self.heartbeat_timeout),
+                },
+                Some(response) => {
+                    match tokio::time::timeout(self.heartbeat_timeout, response.chunk()).await {
+                        Ok(Ok(Some(chunk))) => {
+                            self.buffer.extend_from_slice(&chunk);
+                            self.failures = 0;
+                            continue;
+                        }
+                        Ok(Ok(None)) => Error::StreamClosed,
+                        Ok(Err(e)) => e.into(),
+                        Err(_) => Error::StreamTimeout(self.heartbeat_timeout),
+                    }
+                }
+            };
+
+            // Whatever is left of a partial line is lost with the connection
+            self.response = None;
+            self.buffer.clear();
+            self.failures += 1;
+
+            match self
+                .retry_policy
+                .delay_for(&error, self.failures, CallKind::Idempotent)
+            {
+                Some(delay) => tokio::time::sleep(delay).await,
+                None => {
+                    self.done = true;
+                    return Some(Err(error));
+                }
+            }
+        }
+    }
+
+    async fn connect(&self) -> Result<reqwest::Response, Error> {
+        let response = self.http_client.get(self.url.as_str()).send().await?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let retry_after = parse_retry_after(response.headers());
+            let body = response.bytes().await?;
+
+            return Err(ShodanClient::decode_error(status, retry_after, &body));
+        }
+
+        Ok(response)
+    }
+}
+
+impl Streaming for ShodanClient {
+    fn stream(&self, filter: StreamFilter) -> BannerStream {
+        let (endpoint, parameters) = filter.endpoint();
+
+        BannerStream::new(self, self.build_stream_url(&endpoint, &parameters))
+    }
+
+    fn stream_banners(&self) -> BannerStream {
+        self.stream(StreamFilter::Banners)
+    }
+
+    fn stream_asn(&self, asn: Vec<String>) -> BannerStream {
+        self.stream(StreamFilter::Asn(asn))
+    }
+
+    fn stream_countries(&self, countries: Vec<String>) -> BannerStream {
+        self.stream(StreamFilter::Countries(countries))
+    }
+
+    fn stream_ports(&self, ports: Vec<u16>) -> BannerStream {
+        self.stream(StreamFilter::Ports(ports))
+    }
+
+    fn stream_tags(&self, tags: Vec<String>) -> BannerStream {
+        self.stream(StreamFilter::Tags(tags))
+    }
+
+    fn stream_vulns(&self, vulns: Vec<String>) -> BannerStream {
+        self.stream(StreamFilter::Vulns(vulns))
+    }
+
+    fn stream_custom(&self, query: String) -> BannerStream {
+        self.stream(StreamFilter::Custom(query))
+    }
+
+    fn stream_alerts(&self) -> AlertStream {
+        AlertStream::new(self.stream(StreamFilter::Alerts))
+    }
+
+    fn stream_alert(&self, id: String) -> AlertStream {
+        AlertStream::new(self.stream(StreamFilter::Alert(id)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::{MockResponse, MockServer};
+    use crate::*;
+    use futures::StreamExt;
+    use reqwest::{Method, StatusCode};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    const BANNERS: &str = include_str!("../fixtures/stream_banners.jsonl");
+    const ALERTS: &str = include_str!("../fixtures/stream_alert.jsonl");
+
+    fn fast_retries() -> RetryPolicy {
+        RetryPolicy::default()
+            .initial_backoff(Duration::from_millis(1))
+            .jitter(false)
+    }
+
+    #[tokio::test]
+    async fn can_stream_banners() {
+        let server = MockServer::with_fixtures().await;
+        let banners = server
+            .client()
+            .stream_banners()
+            .take(2)
+            .collect::<Vec<_>>()
+            .await;
+
+        assert_eq!(banners.len(), 2);
+        assert!(banners.iter().all(Result::is_ok));
+    }
+
+    #[tokio::test]
+    async fn reconnects_dropped_stream() {
+        let server = MockServer::with_fixtures().await;
+        let first = BANNERS.lines().next().unwrap();
+        server.mock_once(
+            Method::GET,
+            "/shodan/banners",
+            MockResponse::json(StatusCode::OK, format!("{first}\n\n{}", &first[..20])),
+        );
+
+        let ips = server
+            .client()
+            .stream_banners()
+            .retry_policy(fast_retries())
+            .take(3)
+            .map(|banner| banner.unwrap().ip.to_string())
+            .collect::<Vec<_>>()
+            .await;
+
+        assert_eq!(ips, vec!["142.250.64.78", "142.250.64.78", "8.8.8.8"]);
+        assert_eq!(server.received_requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn reconnects_regardless_of_client_retry_policy() {
+        let server = MockServer::with_fixtures().await;
+        server.mock_once(
+            Method::GET,
+            "/shodan/banners",
+            MockResponse::error(StatusCode::SERVICE_UNAVAILABLE, "Try again later"),
+        );
+
+        let client = ShodanClient::builder(testing::MOCK_API_KEY)
+            .base_url(server.url())
+            .stream_url(server.url())
+            .retry_policy(RetryPolicy::default().max_attempts(1))
+            .build()
+            .unwrap();
+        let banner = client.stream_banners().next().await.unwrap();
+
+        assert!(banner.is_ok());
+        assert_eq!(server.received_requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn times_out_connecting() {
+        // Accepts connections but never answers them
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let accepted = Arc::new(AtomicUsize::new(0));
+        let counter = accepted.clone();
+        tokio::spawn(async move {
+            let mut connections = vec![];
+            while let Ok((connection, _)) = listener.accept().await {
+                counter.fetch_add(1, Ordering::SeqCst);
+                connections.push(connection);
+            }
+        });
+
+        let client = ShodanClient::builder(testing::MOCK_API_KEY)
+            .stream_url(format!("http://{address}"))
+            .build()
+            .unwrap();
+        let results = client
+            .stream_banners()
+            .heartbeat_timeout(Duration::from_millis(50))
+            .retry_policy(fast_retries().max_attempts(2))
+            .collect::<Vec<_>>()
+            .await;
+
+        assert_eq!(results.len(), 1);
+        assert!(matches!(results[0], Err(Error::StreamTimeout(_))));
+        assert_eq!(accepted.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn ends_stream_on_unretryable_error() {
+        let server = MockServer::with_fixtures().await;
+        server.mock(
+            Method::GET,
+            "/shodan/asn/AS15169",
+            MockResponse::error(StatusCode::FORBIDDEN, "Access denied"),
+        );
+
+        let results = server
+            .client()
+            .stream_asn(vec![String::from("AS15169")])
+            .retry_policy(fast_retries())
+            .collect::<Vec<_>>()
+            .await;
+
+        assert_eq!(results.len(), 1);
+        assert!(matches!(results[0], Err(Error::PlanRestriction(_))));
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_attempts() {
+        let server = MockServer::with_fixtures().await;
+        server.mock(
+            Method::GET,
+            "/shodan/countries/NL,BE",
+            MockResponse::error(StatusCode::SERVICE_UNAVAILABLE, "Try again later"),
+        );
+
+        let results = server
+            .client()
+            .stream_countries(vec![String::from("NL"), String::from("BE")])
+            .retry_policy(fast_retries().max_attempts(3))
+            .collect::<Vec<_>>()
+            .await;
+
+        assert!(matches!(results[..], [Err(Error::Server(_))]));
+        assert_eq!(server.received_requests().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn streams_custom_query() {
+        let server = MockServer::with_fixtures().await;
+        server.mock(
+            Method::GET,
+            "/shodan/custom",
+            MockResponse::json(StatusCode::OK, BANNERS),
+        );
+
+        let banner = server
+            .client()
+            .stream_custom(String::from("port:8080 country:NL"))
+            .next()
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(banner.ip.to_string(), "142.250.64.78");
+
+        let request = &server.received_requests()[0];
+        assert_eq!(request.query_param("query"), Some("port:8080 country:NL"));
+    }
+
+    #[tokio::test]
+    async fn can_stream_alert() {
+        let server = MockServer::with_fixtures().await;
+        let event = server
+            .client()
+            .stream_alert(String::from("HKVGAIRWD79Z7W2T"))
+            .next()
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(event.alert_id, "HKVGAIRWD79Z7W2T");
+        assert_eq!(event.alert_name, "DNS Alert");
+        assert_eq!(event.banner.ip.to_string(), "142.250.64.78");
+    }
+
+    #[tokio::test]
+    async fn skips_repeated_alerts_after_reconnect() {
+        let server = MockServer::with_fixtures().await;
+        let lines = ALERTS
+            .lines()
+            .filter(|line| !line.is_empty())
+            .collect::<Vec<_>>();
+        server.mock_once(
+            Method::GET,
+            "/shodan/alert",
+            MockResponse::json(StatusCode::OK, format!("{}\n{}\n", lines[0], lines[1])),
+        );
+        server.mock(
+            Method::GET,
+            "/shodan/alert",
+            MockResponse::json(StatusCode::OK, format!("{}\n{}\n", lines[1], lines[2])),
+        );
+
+        let ports = server
+            .client()
+            .stream_alerts()
+            .retry_policy(fast_retries())
+            .take(3)
+            .map(|event| event.unwrap().banner.port)
+            .collect::<Vec<_>>()
+            .await;
+
+        assert_eq!(ports, vec![80, 443, 853]);
+        assert_eq!(server.received_requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn rejects_banners_without_alert() {
+        let server = MockServer::with_fixtures().await;
+        server.mock(
+            Method::GET,
+            "/shodan/alert",
+            MockResponse::json(StatusCode::OK, BANNERS),
+        );
+
+        let error = server
+            .client()
+            .stream_alerts()
+            .next()
+            .await
+            .unwrap()
+            .unwrap_err();
+
+        assert!(matches!(error, Error::Deserialize(_)));
+    }
+}
@@ -0,0 +1,617 @@
+//! In-process stand-in for the shodan API so that code using the client can be tested offline.
+//!
+//! ```no_run
+//! use shodan_client::testing::MockServer;
+//! use shodan_client::*;
+//!
+//! # async fn example() {
+//! let server = MockServer::with_fixtures().await;
+//! let client = server.client();
+//!
+//! let info = client.get_api_info().await.unwrap();
+//! assert_eq!(info.plan, "dev");
+//! # }
+//! ```
+
+use crate::ShodanClient;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Request, Response, Server};
+use reqwest::header::{HeaderMap, CONTENT_RANGE, RANGE};
+use reqwest::{Method, StatusCode};
+use std::collections::{HashMap, VecDeque};
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+/// API key the clients handed out by `MockServer::client` use.
+pub const MOCK_API_KEY: &str = "MOCK-API-KEY";
+
+pub struct Fixture {
+    pub method: &'static str,
+    pub path: &'static str,
+    pub body: &'static str,
+}
+
+/// Recorded responses for every endpoint the client supports.
+pub const FIXTURES: &[Fixture] = &[
+    Fixture {
+        method: "GET",
+        path: "/account/profile",
+        body: include_str!("../fixtures/account_profile.json"),
+    },
+    Fixture {
+        method: "GET",
+        path: "/api-info",
+        body: include_str!("../fixtures/api_info.json"),
+    },
+    Fixture {
+        method: "GET",
+        path: "/tools/myip",
+        body: include_str!("../fixtures/tools_myip.json"),
+    },
+    Fixture {
+        method: "GET",
+        path: "/tools/httpheaders",
+        body: include_str!("../fixtures/tools_httpheaders.json"),
+    },
+    Fixture {
+        method: "GET",
+        path: "/shodan/ports",
+        body: include_str!("../fixtures/shodan_ports.json"),
+    },
+    Fixture {
+        method: "GET",
+        path: "/shodan/protocols",
+        body: include_str!("../fixtures/shodan_protocols.json"),
+    },
+    Fixture {
+        method: "POST",
+        path: "/shodan/scan",
+        body: include_str!("../fixtures/shodan_scan.json"),
+    },
+    Fixture {
+        method: "POST",
+        path: "/shodan/scan/internet",
+        body: include_str!("../fixtures/shodan_scan_internet.json"),
+    },
+    Fixture {
+        method: "GET",
+        path: "/shodan/scans",
+        body: include_str!("../fixtures/shodan_scans.json"),
+    },
+    Fixture {
+        method: "GET",
+        path: "/shodan/scan/R2XRT5HH6X67PFAB",
+        body: include_str!("../fixtures/shodan_scan_R2XRT5HH6X67PFAB.json"),
+    },
+    Fixture {
+        method: "GET",
+        path: "/shodan/host/8.8.8.8",
+        body: include_str!("../fixtures/shodan_host_8.8.8.8.json"),
+    },
+    Fixture {
+        method: "GET",
+        path: "/shodan/host/198.51.100.7",
+        body: include_str!("../fixtures/shodan_host_198.51.100.7.json"),
+    },
+    Fixture {
+        method: "GET",
+        path: "/shodan/host/count",
+        body: include_str!("../fixtures/shodan_host_count.json"),
+    },
+    Fixture {
+        method: "GET",
+        path: "/shodan/host/search",
+        body: include_str!("../fixtures/shodan_host_search.json"),
+    },
+    Fixture {
+        method: "GET",
+        path: "/shodan/host/search/facets",
+        body: include_str!("../fixtures/shodan_host_search_facets.json"),
+    },
+    Fixture {
+        method: "GET",
+        path: "/shodan/host/search/filters",
+        body: include_str!("../fixtures/shodan_host_search_filters.json"),
+    },
+    Fixture {
+        method: "GET",
+        path: "/shodan/host/search/tokens",
+        body: include_str!("../fixtures/shodan_host_search_tokens.json"),
+    },
+    Fixture {
+        method: "POST",
+        path: "/shodan/alert",
+        body: include_str!("../fixtures/shodan_alert.json"),
+    },
+    Fixture {
+        method: "GET",
+        path: "/shodan/alert/info",
+        body: include_str!("../fixtures/shodan_alert_info.json"),
+    },
+    Fixture {
+        method: "GET",
+        path: "/shodan/alert/triggers",
+        body: include_str!("../fixtures/shodan_alert_triggers.json"),
+    },
+    Fixture {
+        method: "GET",
+        path: "/shodan/alert/HKVGAIRWD79Z7W2T/info",
+        body: include_str!("../fixtures/shodan_alert.json"),
+    },
+    Fixture {
+        method: "POST",
+        path: "/shodan/alert/HKVGAIRWD79Z7W2T",
+        body: include_str!("../fixtures/shodan_alert.json"),
+    },
+    Fixture {
+        method: "DELETE",
+        path: "/shodan/alert/HKVGAIRWD79Z7W2T",
+        body: include_str!("../fixtures/success.json"),
+    },
+    Fixture {
+        method: "PUT",
+        path: "/shodan/alert/HKVGAIRWD79Z7W2T/trigger/malware",
+        body: include_str!("../fixtures/success.json"),
+    },
+    Fixture {
+        method: "DELETE",
+        path: "/shodan/alert/HKVGAIRWD79Z7W2T/trigger/malware",
+        body: include_str!("../fixtures/success.json"),
+    },
+    Fixture {
+        method: "PUT",
+        path: "/shodan/alert/HKVGAIRWD79Z7W2T/trigger/malware/ignore/198.20.88.14:22",
+        body: include_str!("../fixtures/success.json"),
+    },
+    Fixture {
+        method: "DELETE",
+        path: "/shodan/alert/HKVGAIRWD79Z7W2T/trigger/malware/ignore/198.20.88.14:22",
+        body: include_str!("../fixtures/success.json"),
+    },
+    Fixture {
+        method: "PUT",
+        path: "/shodan/alert/HKVGAIRWD79Z7W2T/notifier/default",
+        body: include_str!("../fixtures/success.json"),
+    },
+    Fixture {
+        method: "DELETE",
+        path: "/shodan/alert/HKVGAIRWD79Z7W2T/notifier/default",
+        body: include_str!("../fixtures/success.json"),
+    },
+    Fixture {
+        method: "PUT",
+        path: "/shodan/alert/HKVGAIRWD79Z7W2T/notifier/7xkHQ2SNS4xmH3Ka",
+        body: include_str!("../fixtures/success.json"),
+    },
+    Fixture {
+        method: "GET",
+        path: "/notifier",
+        body: include_str!("../fixtures/notifier.json"),
+    },
+    Fixture {
+        method: "GET",
+        path: "/notifier/provider",
+        body: include_str!("../fixtures/notifier_provider.json"),
+    },
+    Fixture {
+        method: "POST",
+        path: "/notifier",
+        body: include_str!("../fixtures/notifier_create.json"),
+    },
+    Fixture {
+        method: "GET",
+        path: "/notifier/7xkHQ2SNS4xmH3Ka",
+        body: include_str!("../fixtures/notifier_7xkHQ2SNS4xmH3Ka.json"),
+    },
+    Fixture {
+        method: "PUT",
+        path: "/notifier/7xkHQ2SNS4xmH3Ka",
+        body: include_str!("../fixtures/success.json"),
+    },
+    Fixture {
+        method: "DELETE",
+        path: "/notifier/7xkHQ2SNS4xmH3Ka",
+        body: include_str!("../fixtures/success.json"),
+    },
+    Fixture {
+        method: "GET",
+        path: "/shodan/data",
+        body: include_str!("../fixtures/shodan_data.json"),
+    },
+    Fixture {
+        method: "GET",
+        path: "/shodan/data/raw-daily",
+        body: include_str!("../fixtures/shodan_data_raw-daily.json"),
+    },
+    Fixture {
+        method: "GET",
+        path: "/org",
+        body: include_str!("../fixtures/org.json"),
+    },
+    Fixture {
+        method: "PUT",
+        path: "/org/member/asmith",
+        body: include_str!("../fixtures/success.json"),
+    },
+    Fixture {
+        method: "DELETE",
+        path: "/org/member/asmith",
+        body: include_str!("../fixtures/success.json"),
+    },
+    Fixture {
+        method: "GET",
+        path: "/shodan/banners",
+        body: include_str!("../fixtures/stream_banners.jsonl"),
+    },
+    Fixture {
+        method: "GET",
+        path: "/shodan/alert",
+        body: include_str!("../fixtures/stream_alert.jsonl"),
+    },
+    Fixture {
+        method: "GET",
+        path: "/shodan/alert/HKVGAIRWD79Z7W2T",
+        body: include_str!("../fixtures/stream_alert.jsonl"),
+    },
+    Fixture {
+        method: "GET",
+        path: "/shodan/query",
+        body: include_str!("../fixtures/shodan_query.json"),
+    },
+    Fixture {
+        method: "GET",
+        path: "/shodan/query/search",
+        body: include_str!("../fixtures/shodan_query_search.json"),
+    },
+    Fixture {
+        method: "GET",
+        path: "/shodan/query/tags",
+        body: include_str!("../fixtures/shodan_query_tags.json"),
+    },
+    Fixture {
+        method: "GET",
+        path: "/dns/domain/google.com",
+        body: include_str!("../fixtures/dns_domain_google.com.json"),
+    },
+    Fixture {
+        method: "GET",
+        path: "/dns/resolve",
+        body: include_str!("../fixtures/dns_resolve.json"),
+    },
+    Fixture {
+        method: "GET",
+        path: "/dns/reverse",
+        body: include_str!("../fixtures/dns_reverse.json"),
+    },
+];
+
+#[derive(Debug, Clone)]
+pub struct MockResponse {
+    status: StatusCode,
+    headers: Vec<(String, String)>,
+    body: String,
+}
+
+impl MockResponse {
+    pub fn json(status: StatusCode, body: impl Into<String>) -> Self {
+        Self {
+            status,
+            headers: vec![("Content-Type".into(), "application/json".into())],
+            body: body.into(),
+        }
+    }
+
+    /// Shorthand for the `{"error": "..."}` bodies shodan returns.
+    pub fn error(status: StatusCode, message: &str) -> Self {
+        Self::json(status, serde_json::json!({ "error": message }).to_string())
+    }
+
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+
+        self
+    }
+}
+
+/// A request as it was received by the mock server.
+#[derive(Debug, Clone)]
+pub struct ReceivedRequest {
+    pub method: Method,
+    pub path: String,
+    pub query: Vec<(String, String)>,
+    pub headers: HeaderMap,
+    pub body: Vec<u8>,
+}
+
+impl ReceivedRequest {
+    pub fn query_param(&self, name: &str) -> Option<&str> {
+        self.query
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+#[derive(Default)]
+struct Route {
+    once: VecDeque<MockResponse>,
+    sticky: Option<MockResponse>,
+}
+
+#[derive(Default)]
+struct MockFile {
+    contents: Vec<u8>,
+    /// Downloads to cut short, after how many bytes and whether to hang up or go quiet.
+    interruptions: VecDeque<(usize, Interruption)>,
+}
+
+enum Interruption {
+    Drop,
+    Stall,
+}
+
+#[derive(Default)]
+struct State {
+    routes: HashMap<(Method, String), Route>,
+    files: HashMap<String, MockFile>,
+    received: Vec<ReceivedRequest>,
+}
+
+/// Local HTTP server that answers API calls with canned responses. Requests are matched on
+/// method and path only, the query string is recorded but otherwise ignored. The server shuts
+/// down when dropped.
+pub struct MockServer {
+    address: SocketAddr,
+    state: Arc<Mutex<State>>,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+impl MockServer {
+    /// Starts a server without any routes configured.
+    pub async fn start() -> Self {
+        let state = Arc::new(Mutex::new(State::default()));
+
+        let service_state = state.clone();
+        let make_service = make_service_fn(move |_| {
+            let state = service_state.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| {
+                    handle_request(state.clone(), request)
+                }))
+            }
+        });
+
+        let server = Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0))).serve(make_service);
+        let address = server.local_addr();
+
+        let (shutdown, shutdown_signal) = oneshot::channel::<()>();
+        tokio::spawn(server.with_graceful_shutdown(async {
+            shutdown_signal.await.ok();
+        }));
+
+        Self {
+            address,
+            state,
+            shutdown: Some(shutdown),
+        }
+    }
+
+    /// Starts a server that serves the fixture corpus in `FIXTURES`.
+    pub async fn with_fixtures() -> Self {
+        let server = Self::start().await;
+        for fixture in FIXTURES {
+            let method = Method::from_bytes(fixture.method.as_bytes())
+                .expect("Fixture methods should be valid");
+
+            server.mock(
+                method,
+                fixture.path,
+                MockResponse::json(StatusCode::OK, fixture.body),
+            );
+        }
+
+        server
+    }
+
+    pub fn url(&self) -> String {
+        format!("http://{}", self.address)
+    }
+
+    /// Creates a client that talks to this server.
+    pub fn client(&self) -> ShodanClient {
+        ShodanClient::builder(MOCK_API_KEY)
+            .base_url(self.url())
+            .stream_url(self.url())
+            .build()
+            .expect("Mock server URL should be valid")
+    }
+
+    /// Answers every request to `path` with `response`, replacing any earlier mock.
+    pub fn mock(&self, method: Method, path: impl Into<String>, response: MockResponse) {
+        let mut state = self.state.lock().unwrap();
+        state
+            .routes
+            .entry((method, path.into()))
+            .or_default()
+            .sticky = Some(response);
+    }
+
+    /// Answers the next request to `path` with `response`. Queued responses are served in order
+    /// before falling back to the response set with `mock`.
+    pub fn mock_once(&self, method: Method, path: impl Into<String>, response: MockResponse) {
+        let mut state = self.state.lock().unwrap();
+        state
+            .routes
+            .entry((method, path.into()))
+            .or_default()
+            .once
+            .push_back(response);
+    }
+
+    /// Serves `contents` as a download at `path`, like the bulk data files shodan links to.
+    /// Files don't require an API key and honour `Range` requests, answering ranges that start
+    /// past the end with a 416 like S3 does.
+    pub fn serve_file(&self, path: impl Into<String>, contents: impl Into<Vec<u8>>) {
+        let mut state = self.state.lock().unwrap();
+        state.files.insert(
+            path.into(),
+            MockFile {
+                contents: contents.into(),
+                interruptions: VecDeque::new(),
+            },
+        );
+    }
+
+    /// Drops the connection of the next download of `path` after `after` bytes of the body.
+    pub fn interrupt_file(&self, path: &str, after: usize) {
+        let mut state = self.state.lock().unwrap();
+        state
+            .files
+            .get_mut(path)
+            .expect("File should be served before it can be interrupted")
+            .interruptions
+            .push_back((after, Interruption::Drop));
+    }
+
+    /// Stops sending the next download of `path` after `after` bytes of the body while keeping
+    /// the connection open, like a stalled transfer.
+    pub fn stall_file(&self, path: &str, after: usize) {
+        let mut state = self.state.lock().unwrap();
+        state
+            .files
+            .get_mut(path)
+            .expect("File should be served before it can be stalled")
+            .interruptions
+            .push_back((after, Interruption::Stall));
+    }
+
+    /// All requests received so far, in order of arrival.
+    pub fn received_requests(&self) -> Vec<ReceivedRequest> {
+        self.state.lock().unwrap().received.clone()
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            shutdown.send(()).ok();
+        }
+    }
+}
+
+async fn handle_request(
+    state: Arc<Mutex<State>>,
+    request: Request<Body>,
+) -> Result<Response<Body>, Infallible> {
+    let (parts, body) = request.into_parts();
+    let body = hyper::body::to_bytes(body).await.unwrap_or_default();
+
+    let query = url::form_urlencoded::parse(parts.uri.query().unwrap_or_default().as_bytes())
+        .into_owned()
+        .collect::<Vec<_>>();
+
+    let received = ReceivedRequest {
+        method: parts.method.clone(),
+        path: parts.uri.path().to_string(),
+        query,
+        headers: parts.headers.clone(),
+        body: body.to_vec(),
+    };
+
+    let response = {
+        let mut state = state.lock().unwrap();
+        if let Some(file) = state.files.get_mut(&received.path) {
+            let response = serve_file(file, &received.headers);
+            state.received.push(received);
+            return Ok(response);
+        }
+
+        let response = if received.query_param("key").is_none() {
+            MockResponse::error(StatusCode::UNAUTHORIZED, "Please provide a valid API key")
+        } else {
+            state
+                .routes
+                .get_mut(&(received.method.clone(), received.path.clone()))
+                .and_then(|route| route.once.pop_front().or_else(|| route.sticky.clone()))
+                .unwrap_or_else(|| {
+                    MockResponse::error(
+                        StatusCode::NOT_FOUND,
+                        format!("No mock for {} {}", received.method, received.path).as_str(),
+                    )
+                })
+        };
+
+        state.received.push(received);
+        response
+    };
+
+    let mut builder = Response::builder().status(response.status);
+    for (name, value) in response.headers {
+        builder = builder.header(name, value);
+    }
+
+    Ok(builder
+        .body(Body::from(response.body))
+        .expect("Mock responses should be valid"))
+}
+
+fn serve_file(file: &mut MockFile, headers: &HeaderMap) -> Response<Body> {
+    let start = headers
+        .get(RANGE)
+        .and_then(|range| {
+            range
+                .to_str()
+                .ok()?
+                .strip_prefix("bytes=")?
+                .strip_suffix('-')
+        })
+        .and_then(|start| start.parse::<usize>().ok());
+
+    if start.is_some_and(|start| start >= file.contents.len()) {
+        return Response::builder()
+            .status(StatusCode::RANGE_NOT_SATISFIABLE)
+            .header(CONTENT_RANGE, format!("bytes */{}", file.contents.len()))
+            .body(Body::empty())
+            .expect("Mock responses should be valid");
+    }
+
+    let contents = file.contents[start.unwrap_or_default()..].to_vec();
+    let mut builder = Response::builder().header("Content-Type", "application/octet-stream");
+    builder = match start {
+        Some(start) => builder.status(StatusCode::PARTIAL_CONTENT).header(
+            CONTENT_RANGE,
+            format!(
+                "bytes {start}-{}/{}",
+                file.contents.len() - 1,
+                file.contents.len()
+            ),
+        ),
+        None => builder.status(StatusCode::OK),
+    };
+
+    let body = match file.interruptions.pop_front() {
+        Some((after, interruption)) => {
+            // Announce the full length but hang up or go quiet partway
+            let (mut sender, body) = Body::channel();
+            builder = builder.header("Content-Length", contents.len());
+            tokio::spawn(async move {
+                let sent = contents[..after.min(contents.len())].to_vec();
+                sender.send_data(sent.into()).await.ok();
+                match interruption {
+                    // Give hyper a moment to flush what was sent, then hang up
+                    Interruption::Drop => tokio::time::sleep(Duration::from_millis(50)).await,
+                    Interruption::Stall => tokio::time::sleep(Duration::from_secs(3600)).await,
+                }
+                sender.abort();
+            });
+
+            body
+        }
+        None => Body::from(contents),
+    };
+
+    builder.body(body).expect("Mock responses should be valid")
+}
@@ -0,0 +1,327 @@
+use crate::*;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::time::Duration;
+
+/// The banners of a host laid out over time per service, ex: from `host_ip` with `history`
+/// enabled. Used to find out what changed on a host between two points in time.
+#[derive(Debug)]
+pub struct HostTimeline {
+    services: BTreeMap<ServiceKey, Vec<Banner>>,
+    max_age: Duration,
+}
+
+/// A service on a host, ex: `443/tcp`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ServiceKey {
+    pub port: i64,
+    pub transport: String,
+}
+
+impl fmt::Display for ServiceKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.port, self.transport)
+    }
+}
+
+/// What changed on a host between two points of a `HostTimeline`.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct HostDiff {
+    pub opened: Vec<ServiceKey>,
+    pub closed: Vec<ServiceKey>,
+    pub changed: Vec<ServiceChange>,
+    pub certificate_rotations: Vec<CertificateRotation>,
+    pub new_vulns: Vec<String>,
+}
+
+impl HostDiff {
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+/// A service that started reporting a different product or version.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ServiceChange {
+    pub service: ServiceKey,
+    pub before: ServiceVersion,
+    pub after: ServiceVersion,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ServiceVersion {
+    pub product: Option<String>,
+    pub version: Option<String>,
+}
+
+/// A service that started presenting a different certificate, identified by SHA256 fingerprint.
+#[derive(Debug, PartialEq, Eq)]
+pub struct CertificateRotation {
+    pub service: ServiceKey,
+    pub before: String,
+    pub after: String,
+}
+
+impl HostTimeline {
+    /// Banners with a timestamp that can't be read are left out, as there's no telling how
+    /// old they are.
+    pub fn new(banners: impl IntoIterator<Item = Banner>) -> Self {
+        let mut services = BTreeMap::<_, Vec<_>>::new();
+        for banner in banners {
+            if timestamp::seconds(&banner.timestamp).is_none() {
+                continue;
+            }
+
+            let key = ServiceKey {
+                port: banner.port,
+                transport: banner.transport.clone(),
+            };
+            services.entry(key).or_default().push(banner);
+        }
+
+        for banners in services.values_mut() {
+            sort_by_timestamp(banners);
+        }
+
+        Self {
+            services,
+            max_age: Duration::from_secs(30 * 24 * 60 * 60),
+        }
+    }
+
+    /// How long a service counts as open after the last banner seen for it, defaults to 30
+    /// days. Shodan doesn't record services going away, they simply stop showing up.
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = max_age;
+
+        self
+    }
+
+    /// Banners of every service, oldest first.
+    pub fn services(&self) -> impl Iterator<Item = (&ServiceKey, &[Banner])> {
+        self.services
+            .iter()
+            .map(|(key, banners)| (key, banners.as_slice()))
+    }
+
+    /// Every distinct banner timestamp, oldest first. These are the points that can be diffed.
+    /// Timestamps within the same second count as one.
+    pub fn timestamps(&self) -> Vec<&str> {
+        self.services
+            .values()
+            .flatten()
+            .filter_map(|banner| {
+                let seconds = timestamp::seconds(&banner.timestamp)?;
+                Some((seconds, banner.timestamp.as_str()))
+            })
+            .collect::<BTreeMap<_, _>>()
+            .into_values()
+            .collect()
+    }
+
+    /// The services that were open at the given timestamp, formatted like the banner ones, along
+    /// with their latest banner. Empty when the timestamp itself can't be read.
+    pub fn at(&self, timestamp: &str) -> BTreeMap<&ServiceKey, &Banner> {
+        let Some(at) = timestamp::seconds(timestamp) else {
+            return BTreeMap::new();
+        };
+
+        self.services
+            .iter()
+            .filter_map(|(key, banners)| {
+                let (banner, seen) = banners.iter().rev().find_map(|banner| {
+                    let seen = timestamp::seconds(&banner.timestamp)?;
+                    (seen <= at).then_some((banner, seen))
+                })?;
+
+                (at - seen <= self.max_age.as_secs() as i64).then_some((key, banner))
+            })
+            .collect()
+    }
+
+    pub fn diff(&self, from: &str, to: &str) -> HostDiff {
+        let before = self.at(from);
+        let after = self.at(to);
+
+        let mut diff = HostDiff {
+            opened: after
+                .keys()
+                .filter(|key| !before.contains_key(*key))
+                .map(|key| (*key).clone())
+                .collect(),
+            closed: before
+                .keys()
+                .filter(|key| !after.contains_key(*key))
+                .map(|key| (*key).clone())
+                .collect(),
+            ..Default::default()
+        };
+
+        for (key, new) in &after {
+            let Some(old) = before.get(key) else {
+                continue;
+            };
+
+            if old.product != new.product || old.version != new.version {
+                diff.changed.push(ServiceChange {
+                    service: (*key).clone(),
+                    before: ServiceVersion {
+                        product: old.product.clone(),
+                        version: old.version.clone(),
+                    },
+                    after: ServiceVersion {
+                        product: new.product.clone(),
+                        version: new.version.clone(),
+                    },
+                });
+            }
+
+            if let (Some(old_ssl), Some(new_ssl)) = (&old.ssl, &new.ssl) {
+                let (old_cert, new_cert) = (
+                    &old_ssl.cert.fingerprint.sha256,
+                    &new_ssl.cert.fingerprint.sha256,
+                );
+                if old_cert != new_cert {
+                    diff.certificate_rotations.push(CertificateRotation {
+                        service: (*key).clone(),
+                        before: old_cert.clone(),
+                        after: new_cert.clone(),
+                    });
+                }
+            }
+        }
+
+        let vulns = |banners: &BTreeMap<&ServiceKey, &Banner>| {
+            banners
+                .values()
+                .filter_map(|banner| banner.vulns.as_ref())
+                .flat_map(|vulns| vulns.keys().cloned())
+                .collect::<BTreeSet<_>>()
+        };
+        diff.new_vulns = vulns(&after).difference(&vulns(&before)).cloned().collect();
+
+        diff
+    }
+}
+
+impl From<SearchHostIpResponse> for HostTimeline {
+    fn from(response: SearchHostIpResponse) -> Self {
+        Self::new(response.data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::banner;
+    use crate::*;
+    use serde_json::json;
+    use std::time::Duration;
+
+    fn timeline() -> HostTimeline {
+        let (january, february) = ("2023-01-01T10:00:00.000000", "2023-02-20T10:00:00.000000");
+        let mut rotated = banner(1, json!({ "timestamp": february }));
+        rotated.ssl.as_mut().unwrap().cert.fingerprint.sha256 = String::from("rotated");
+
+        HostTimeline::new([
+            banner(0, json!({ "timestamp": january })),
+            banner(
+                0,
+                json!({"timestamp": february, "product": "nginx", "version": "1.22.1"}),
+            ),
+            banner(1, json!({"timestamp": january, "vulns": {}})),
+            rotated,
+            banner(0, json!({"timestamp": january, "port": 8080})),
+        ])
+    }
+
+    #[test]
+    fn orders_banners_per_service() {
+        let timeline = timeline();
+
+        let services = timeline
+            .services()
+            .map(|(key, banners)| (key.to_string(), banners.len()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            services,
+            vec![
+                (String::from("80/tcp"), 2),
+                (String::from("443/tcp"), 2),
+                (String::from("8080/tcp"), 1)
+            ]
+        );
+        assert_eq!(
+            timeline.timestamps(),
+            vec!["2023-01-01T10:00:00.000000", "2023-02-20T10:00:00.000000"]
+        );
+    }
+
+    #[test]
+    fn diffs_points_in_time() {
+        let timeline = timeline();
+        let service = |port| ServiceKey {
+            port,
+            transport: String::from("tcp"),
+        };
+
+        let diff = timeline.diff("2023-01-01T10:00:00.000000", "2023-02-20T10:00:00.000000");
+        assert!(diff.opened.is_empty());
+        assert_eq!(diff.closed, vec![service(8080)]);
+        assert_eq!(
+            diff.changed,
+            vec![ServiceChange {
+                service: service(80),
+                before: ServiceVersion {
+                    product: Some(String::from("Google Web Server")),
+                    version: None,
+                },
+                after: ServiceVersion {
+                    product: Some(String::from("nginx")),
+                    version: Some(String::from("1.22.1")),
+                },
+            }]
+        );
+        assert_eq!(diff.certificate_rotations.len(), 1);
+        assert_eq!(diff.certificate_rotations[0].after, "rotated");
+        assert_eq!(diff.new_vulns, vec!["CVE-2021-44228"]);
+
+        assert!(timeline
+            .diff("2023-02-20T10:00:00.000000", "2023-02-20T10:00:00.000000")
+            .is_empty());
+    }
+
+    #[test]
+    fn keeps_services_open_for_max_age() {
+        let timeline = timeline().max_age(Duration::from_secs(365 * 24 * 60 * 60));
+
+        assert_eq!(timeline.at("2023-02-20T10:00:00.000000").len(), 3);
+        assert!(timeline.at("2022-12-31T10:00:00.000000").is_empty());
+    }
+
+    #[test]
+    fn leaves_out_unreadable_timestamps() {
+        let timeline = HostTimeline::new([
+            banner(0, json!({ "timestamp": "2023-01-01T10:00:00.000000" })),
+            banner(1, json!({ "timestamp": "yesterday" })),
+        ]);
+
+        assert_eq!(timeline.services().count(), 1);
+        assert_eq!(timeline.at("2023-01-02T10:00:00.000000").len(), 1);
+        assert!(timeline.at("tomorrow").is_empty());
+    }
+
+    #[test]
+    fn compares_timestamps_as_points_in_time() {
+        let timeline = HostTimeline::new([
+            banner(0, json!({ "timestamp": "2023-01-01T10:00:00.000000" })),
+            banner(1, json!({ "timestamp": "2023-01-01T10:00:00" })),
+            banner(0, json!({ "timestamp": "2022-12-31T10:00:00.5" })),
+        ]);
+
+        assert_eq!(
+            timeline.timestamps(),
+            vec!["2022-12-31T10:00:00.5", "2023-01-01T10:00:00"]
+        );
+        assert_eq!(timeline.at("2023-01-01T10:00:00").len(), 2);
+    }
+}
@@ -0,0 +1,140 @@
+/// Parses a timestamp into UTC, `None` for formats that aren't known.
+#[cfg(feature = "chrono")]
+pub(crate) fn parse(value: &str) -> Option<chrono::DateTime<chrono::Utc>> {
+    use chrono::{DateTime, NaiveDateTime, Utc};
+
+    // Banners and most other responses leave out the zone, certificates use ASN.1 times and
+    // OCSP responses separate the date and time with a space.
+    const FORMATS: &[&str] = &[
+        "%Y-%m-%dT%H:%M:%S%.f",
+        "%Y%m%d%H%M%SZ",
+        "%Y-%m-%d %H:%M:%S%.f",
+    ];
+
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(value) {
+        return Some(timestamp.with_timezone(&Utc));
+    }
+
+    FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .map(|timestamp| timestamp.and_utc())
+}
+
+/// Seconds since the epoch of an ISO 8601 timestamp without zone, ex: the
+/// `2023-03-01T17:46:53.604981` of banners. `None` for anything else, including dates and times
+/// that don't exist. This doesn't use chrono so enabling the feature can't change the outcome.
+pub(crate) fn seconds(timestamp: &str) -> Option<i64> {
+    let (date, time) = timestamp.split_once('T')?;
+    let (time, fraction) = time.split_once('.').unwrap_or((time, "0"));
+    if fraction.is_empty() || !fraction.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+
+    let [year, month, day] = date.split('-').collect::<Vec<_>>()[..] else {
+        return None;
+    };
+    let [hours, minutes, seconds] = time.split(':').collect::<Vec<_>>()[..] else {
+        return None;
+    };
+    let (year, month, day) = (digits(year, 4)?, digits(month, 2)?, digits(day, 2)?);
+    let (hours, minutes, seconds) = (digits(hours, 2)?, digits(minutes, 2)?, digits(seconds, 2)?);
+
+    if !(1..=days_in_month(year, month)?).contains(&day)
+        || hours > 23
+        || minutes > 59
+        || seconds > 59
+    {
+        return None;
+    }
+
+    // Days since the epoch, see http://howardhinnant.github.io/date_algorithms.html
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146097 + day_of_era - 719468;
+
+    Some(days * 86400 + hours * 3600 + minutes * 60 + seconds)
+}
+
+/// `None` when `month` isn't one.
+pub(crate) fn days_in_month(year: i64, month: i64) -> Option<i64> {
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+
+    match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => Some(31),
+        4 | 6 | 9 | 11 => Some(30),
+        2 if leap => Some(29),
+        2 => Some(28),
+        _ => None,
+    }
+}
+
+/// Parses a number of exactly `length` digits.
+fn digits(value: &str, length: usize) -> Option<i64> {
+    if value.len() != length || !value.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+
+    value.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::timestamp::*;
+
+    #[test]
+    fn reads_shodan_formats() {
+        assert_eq!(seconds("2023-03-01T17:46:53.604981"), Some(1677692813));
+        assert_eq!(seconds("2010-03-15T07:42:50"), Some(1268638970));
+        assert_eq!(seconds("yesterday"), None);
+    }
+
+    #[test]
+    fn rejects_impossible_timestamps() {
+        assert_eq!(seconds("2024-02-29T10:00:00"), Some(1709200800));
+        for timestamp in [
+            "2023-13-45T10:00:00",
+            "2023-02-29T10:00:00",
+            "2023-04-31T10:00:00",
+            "2023-03-01T24:00:00",
+            "2023-03-01T17:46:53.",
+            "2023-03-01T17:46:53+02:00",
+        ] {
+            assert_eq!(seconds(timestamp), None, "{timestamp}");
+        }
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn reads_certificate_and_ocsp_formats() {
+        use chrono::{TimeZone, Utc};
+
+        assert_eq!(
+            parse("20230501082205Z"),
+            Utc.with_ymd_and_hms(2023, 5, 1, 8, 22, 5).single()
+        );
+        assert_eq!(
+            parse("2023-02-27 09:21:00"),
+            Utc.with_ymd_and_hms(2023, 2, 27, 9, 21, 0).single()
+        );
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn exposes_parsed_timestamps() {
+        let banner = crate::tests::banner(1, serde_json::json!({}));
+        assert_eq!(banner.timestamp, "2023-03-01T09:12:44.117203");
+        assert_eq!(
+            banner
+                .timestamp_utc()
+                .map(|timestamp| timestamp.timestamp()),
+            seconds(&banner.timestamp)
+        );
+
+        let cert = &banner.ssl.unwrap().cert;
+        assert!(cert.issued_utc().unwrap() < cert.expires_utc().unwrap());
+    }
+}
@@ -0,0 +1,479 @@
+use crate::Error;
+use async_trait::async_trait;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE, RETRY_AFTER};
+use reqwest::{Method, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use url::Url;
+
+/// A single API call as handed to a `Transport`.
+#[derive(Debug, Clone)]
+pub struct TransportRequest {
+    pub method: Method,
+    pub url: String,
+    pub body: Option<RequestBody>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum RequestBody {
+    /// Sent as `application/x-www-form-urlencoded`.
+    Form(Vec<(String, String)>),
+    Json(serde_json::Value),
+}
+
+#[derive(Debug, Clone)]
+pub struct TransportResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Vec<u8>,
+}
+
+/// Sends requests on behalf of the client. Rate limiting and retries happen before a request
+/// reaches the transport, so implementations only need to perform the actual exchange.
+#[async_trait]
+pub trait Transport: Send + Sync {
+    async fn send(&self, request: TransportRequest) -> Result<TransportResponse, Error>;
+}
+
+/// Default transport that goes out over the network with reqwest.
+pub struct ReqwestTransport {
+    http_client: reqwest::Client,
+}
+
+impl ReqwestTransport {
+    pub fn new(http_client: reqwest::Client) -> Self {
+        Self { http_client }
+    }
+}
+
+#[async_trait]
+impl Transport for ReqwestTransport {
+    async fn send(&self, request: TransportRequest) -> Result<TransportResponse, Error> {
+        let mut builder = self.http_client.request(request.method, request.url);
+        match request.body {
+            Some(RequestBody::Form(form)) => builder = builder.form(&form),
+            Some(RequestBody::Json(json)) => builder = builder.json(&json),
+            None => {}
+        }
+
+        let response = builder.send().await?;
+
+        Ok(TransportResponse {
+            status: response.status(),
+            headers: response.headers().clone(),
+            body: response.bytes().await?.to_vec(),
+        })
+    }
+}
+
+/// Request/response pairs recorded by a `RecordingTransport`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Cassette {
+    pub interactions: Vec<Interaction>,
+}
+
+impl Cassette {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Ok(serde_json::from_slice(&fs::read(path)?)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        Ok(fs::write(path, serde_json::to_vec_pretty(self)?)?)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Interaction {
+    pub request: RecordedRequest,
+    pub response: RecordedResponse,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedRequest {
+    pub method: String,
+    /// Request URL with the API key removed and the query parameters sorted.
+    pub url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<RequestBody>,
+}
+
+impl RecordedRequest {
+    fn from_request(request: &TransportRequest) -> Result<Self, Error> {
+        Ok(Self {
+            method: request.method.to_string(),
+            url: scrub_url(request.url.as_str())?,
+            body: request.body.as_ref().map(scrub_body),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+    /// Set when `body` is base64 encoded because the response wasn't valid UTF-8.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub base64: bool,
+}
+
+impl RecordedResponse {
+    fn from_response(response: &TransportResponse) -> Self {
+        // Only the headers the client actually looks at are worth keeping around
+        let headers = [CONTENT_TYPE, RETRY_AFTER]
+            .iter()
+            .filter_map(|name| {
+                let value = response.headers.get(name)?.to_str().ok()?;
+                Some((name.to_string(), value.to_string()))
+            })
+            .collect();
+
+        let (body, base64) = match String::from_utf8(response.body.clone()) {
+            Ok(body) => (body, false),
+            Err(_) => (BASE64.encode(&response.body), true),
+        };
+
+        Self {
+            status: response.status.as_u16(),
+            headers,
+            body,
+            base64,
+        }
+    }
+
+    fn to_response(&self) -> Result<TransportResponse, Error> {
+        let status = StatusCode::from_u16(self.status)
+            .map_err(|_| Error::Cassette(format!("Invalid status code {}", self.status)))?;
+
+        let mut headers = HeaderMap::new();
+        for (name, value) in &self.headers {
+            let name = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|_| Error::Cassette(format!("Invalid header name {name}")))?;
+            let value = HeaderValue::from_str(value)
+                .map_err(|_| Error::Cassette(format!("Invalid header value {value}")))?;
+            headers.insert(name, value);
+        }
+
+        let body = if self.base64 {
+            BASE64
+                .decode(&self.body)
+                .map_err(|_| Error::Cassette(String::from("Invalid base64 response body")))?
+        } else {
+            self.body.clone().into_bytes()
+        };
+
+        Ok(TransportResponse {
+            status,
+            headers,
+            body,
+        })
+    }
+}
+
+/// Removes the API key from a URL and sorts the query so that recorded requests can be matched
+/// regardless of the key and parameter order.
+fn scrub_url(url: &str) -> Result<String, Error> {
+    let mut url = Url::parse(url)?;
+
+    let mut pairs = url
+        .query_pairs()
+        .into_owned()
+        .filter(|(key, _)| key != "key")
+        .collect::<Vec<_>>();
+    pairs.sort();
+
+    url.set_query(None);
+    if !pairs.is_empty() {
+        url.query_pairs_mut().extend_pairs(pairs);
+    }
+
+    Ok(url.to_string())
+}
+
+/// Form fields that carry notifier credentials, ex: from `create_notifier`. Arguments of
+/// `NotifierArgs::Other` providers aren't known up front and are recorded as-is.
+const SECRET_FORM_FIELDS: &[&str] = &["token", "routing_key", "webhook_url"];
+
+/// Replaces the values of known secret form fields so they don't end up in a cassette.
+fn scrub_body(body: &RequestBody) -> RequestBody {
+    match body {
+        RequestBody::Form(form) => RequestBody::Form(
+            form.iter()
+                .map(|(key, value)| {
+                    if SECRET_FORM_FIELDS.contains(&key.as_str()) {
+                        (key.clone(), String::from("REDACTED"))
+                    } else {
+                        (key.clone(), value.clone())
+                    }
+                })
+                .collect(),
+        ),
+        RequestBody::Json(json) => RequestBody::Json(json.clone()),
+    }
+}
+
+/// Passes requests on to another transport and writes every exchange to a cassette file. The
+/// cassette is rewritten after each request so it survives a test panicking halfway through.
+/// A failed write is reported on stderr and doesn't fail the request, which already went out.
+/// Use `ShodanClientBuilder::record` to record calls made with the client's own HTTP settings.
+pub struct RecordingTransport {
+    inner: Arc<dyn Transport>,
+    path: PathBuf,
+    cassette: Mutex<Cassette>,
+    /// Amount of interactions in the cassette file, so a slow write can't clobber a newer one.
+    written: tokio::sync::Mutex<usize>,
+}
+
+impl RecordingTransport {
+    pub fn wrap(inner: impl Transport + 'static, path: impl Into<PathBuf>) -> Self {
+        Self::wrap_shared(Arc::new(inner), path)
+    }
+
+    pub(crate) fn wrap_shared(inner: Arc<dyn Transport>, path: impl Into<PathBuf>) -> Self {
+        Self {
+            inner,
+            path: path.into(),
+            cassette: Mutex::new(Cassette::default()),
+            written: tokio::sync::Mutex::new(0),
+        }
+    }
+}
+
+#[async_trait]
+impl Transport for RecordingTransport {
+    async fn send(&self, request: TransportRequest) -> Result<TransportResponse, Error> {
+        let recorded_request = RecordedRequest::from_request(&request)?;
+        let response = self.inner.send(request).await?;
+
+        let (count, contents) = {
+            let mut cassette = self.cassette.lock().unwrap();
+            cassette.interactions.push(Interaction {
+                request: recorded_request,
+                response: RecordedResponse::from_response(&response),
+            });
+
+            (
+                cassette.interactions.len(),
+                serde_json::to_vec_pretty(&*cassette)?,
+            )
+        };
+
+        let mut written = self.written.lock().await;
+        if count > *written {
+            match tokio::fs::write(&self.path, contents).await {
+                Ok(()) => *written = count,
+                Err(e) => eprintln!("Failed to write cassette {}: {e}", self.path.display()),
+            }
+        }
+
+        Ok(response)
+    }
+}
+
+/// Serves responses from a cassette instead of going out over the network. Identical requests
+/// are answered in the order they were recorded, with the last recording being reused once
+/// they run out. Requests that weren't recorded at all fail with `Error::UnmatchedRequest`.
+pub struct ReplayTransport {
+    interactions: Mutex<Vec<(Interaction, bool)>>,
+}
+
+impl ReplayTransport {
+    pub fn new(cassette: Cassette) -> Self {
+        Self {
+            interactions: Mutex::new(
+                cassette
+                    .interactions
+                    .into_iter()
+                    .map(|interaction| (interaction, false))
+                    .collect(),
+            ),
+        }
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Ok(Self::new(Cassette::load(path)?))
+    }
+}
+
+#[async_trait]
+impl Transport for ReplayTransport {
+    async fn send(&self, request: TransportRequest) -> Result<TransportResponse, Error> {
+        let recorded_request = RecordedRequest::from_request(&request)?;
+
+        let mut interactions = self.interactions.lock().unwrap();
+        let matching = interactions
+            .iter()
+            .enumerate()
+            .filter(|(_, (interaction, _))| interaction.request == recorded_request)
+            .map(|(index, _)| index)
+            .collect::<Vec<_>>();
+
+        let index = matching
+            .iter()
+            .find(|index| !interactions[**index].1)
+            .or(matching.last())
+            .copied()
+            .ok_or_else(|| Error::UnmatchedRequest {
+                method: recorded_request.method.clone(),
+                url: recorded_request.url.clone(),
+            })?;
+
+        let (interaction, used) = &mut interactions[index];
+        *used = true;
+        interaction.response.to_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::MockServer;
+    use crate::transport::*;
+    use crate::*;
+
+    fn cassette_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("shodan-rs-{}-{name}.json", std::process::id()))
+    }
+
+    #[tokio::test]
+    async fn records_and_replays() {
+        let path = cassette_path("records_and_replays");
+
+        let server = MockServer::with_fixtures().await;
+        let recording = ShodanClient::builder("SECRET-KEY")
+            .base_url(server.url())
+            .user_agent("shodan-rs-recorder")
+            .record(&path)
+            .build()
+            .unwrap();
+        let recorded = recording.get_api_info().await.unwrap();
+        recording
+            .dns_resolve(vec![String::from("google.com")])
+            .await
+            .unwrap();
+        assert!(server
+            .received_requests()
+            .iter()
+            .all(|request| request.headers["user-agent"] == "shodan-rs-recorder"));
+        drop(server);
+
+        let cassette = std::fs::read_to_string(&path).unwrap();
+        assert!(!cassette.contains("SECRET-KEY"));
+
+        let replaying = ShodanClient::builder("OTHER-KEY")
+            .base_url(recording.base_url.as_str())
+            .transport(ReplayTransport::from_file(&path).unwrap())
+            .build()
+            .unwrap();
+        let replayed = replaying.get_api_info().await.unwrap();
+        assert_eq!(recorded.plan, replayed.plan);
+        replaying
+            .dns_resolve(vec![String::from("google.com")])
+            .await
+            .unwrap();
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn fails_on_unmatched_requests() {
+        let client = ShodanClient::builder("KEY")
+            .transport(ReplayTransport::new(Cassette::default()))
+            .build()
+            .unwrap();
+
+        let error = client.get_api_info().await.unwrap_err();
+        assert!(matches!(
+            error,
+            Error::UnmatchedRequest { url, .. } if url == "https://api.shodan.io/api-info"
+        ));
+    }
+
+    #[tokio::test]
+    async fn replays_identical_requests_in_order() {
+        let response = |body: &str| RecordedResponse {
+            status: 200,
+            headers: vec![],
+            body: body.into(),
+            base64: false,
+        };
+        let request = RecordedRequest {
+            method: "GET".into(),
+            url: "https://api.shodan.io/tools/myip".into(),
+            body: None,
+        };
+        let cassette = Cassette {
+            interactions: vec![
+                Interaction {
+                    request: request.clone(),
+                    response: response("\"1.1.1.1\""),
+                },
+                Interaction {
+                    request,
+                    response: response("\"2.2.2.2\""),
+                },
+            ],
+        };
+
+        let client = ShodanClient::builder("KEY")
+            .transport(ReplayTransport::new(cassette))
+            .build()
+            .unwrap();
+
+        assert_eq!(client.get_my_ip().await.unwrap().to_string(), "1.1.1.1");
+        assert_eq!(client.get_my_ip().await.unwrap().to_string(), "2.2.2.2");
+        assert_eq!(client.get_my_ip().await.unwrap().to_string(), "2.2.2.2");
+    }
+
+    #[tokio::test]
+    async fn returns_responses_when_the_cassette_cant_be_written() {
+        let server = MockServer::with_fixtures().await;
+        let client = ShodanClient::builder("KEY")
+            .base_url(server.url())
+            .record(cassette_path("missing-directory").join("cassette.json"))
+            .build()
+            .unwrap();
+
+        client.get_api_info().await.unwrap();
+    }
+
+    #[test]
+    fn scrubs_notifier_secrets() {
+        let request = TransportRequest {
+            method: Method::POST,
+            url: String::from("https://api.shodan.io/notifier?key=SECRET-KEY"),
+            body: Some(RequestBody::Form(vec![
+                (String::from("provider"), String::from("telegram")),
+                (String::from("chat_id"), String::from("1234")),
+                (String::from("token"), String::from("SECRET-TOKEN")),
+            ])),
+        };
+
+        let recorded = RecordedRequest::from_request(&request).unwrap();
+        assert_eq!(
+            recorded.body,
+            Some(RequestBody::Form(vec![
+                (String::from("provider"), String::from("telegram")),
+                (String::from("chat_id"), String::from("1234")),
+                (String::from("token"), String::from("REDACTED")),
+            ]))
+        );
+    }
+
+    #[test]
+    fn keeps_binary_bodies_intact() {
+        let body = vec![0x1f, 0x8b, 0x08, 0x00, 0xff];
+        let response = TransportResponse {
+            status: StatusCode::OK,
+            headers: HeaderMap::new(),
+            body: body.clone(),
+        };
+
+        let recorded = RecordedResponse::from_response(&response);
+        assert!(recorded.base64);
+        assert_eq!(recorded.to_response().unwrap().body, body);
+    }
+}
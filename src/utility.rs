@@ -1,37 +1,40 @@
 use crate::*;
 use async_trait::async_trait;
+use std::net::IpAddr;
 
 #[async_trait]
 pub trait Utility {
-    async fn get_my_ip(&self) -> Result<String, Error>;
+    async fn get_my_ip(&self) -> Result<IpAddr, Error>;
     async fn get_http_headers(&self) -> Result<HashMap<String, String>, Error>;
 }
 
 #[async_trait]
 impl Utility for ShodanClient {
-    async fn get_my_ip(&self) -> Result<String, Error> {
-        Self::fetch(self.build_request_url("/tools/myip", &Default::default())?).await
+    async fn get_my_ip(&self) -> Result<IpAddr, Error> {
+        self.fetch(self.build_request_url("/tools/myip", &Default::default())?)
+            .await
     }
 
     async fn get_http_headers(&self) -> Result<HashMap<String, String>, Error> {
-        Self::fetch(self.build_request_url("/tools/httpheaders", &Default::default())?).await
+        self.fetch(self.build_request_url("/tools/httpheaders", &Default::default())?)
+            .await
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::tests::get_test_api_key;
+    use crate::tests::test_client;
     use crate::*;
 
     #[tokio::test]
     async fn can_get_my_ip() {
-        let client = ShodanClient::new(get_test_api_key());
+        let client = test_client().await;
         client.get_my_ip().await.unwrap();
     }
 
     #[tokio::test]
     async fn can_get_http_headers() {
-        let client = ShodanClient::new(get_test_api_key());
+        let client = test_client().await;
         client.get_http_headers().await.unwrap();
     }
 }
@@ -0,0 +1,341 @@
+use crate::*;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::net::IpAddr;
+
+/// Checks queries against the filters Shodan knows about and the plan of the account, so
+/// mistakes surface before a search spends any credits. Build one with `fetch` and hold on to
+/// it, or let the client do that with `ShodanClientBuilder::validate_queries`.
+#[derive(Debug, Clone)]
+pub struct QueryValidator {
+    filters: HashSet<String>,
+    plan: Option<String>,
+    /// Filters only available on some plans, along with the plans that may use them.
+    restricted: HashMap<String, Vec<String>>,
+}
+
+/// Something wrong with a query as found by `QueryValidator`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QueryIssue {
+    UnknownFilter(String),
+    PlanRestricted {
+        filter: String,
+        plan: String,
+    },
+    InvalidValue {
+        filter: String,
+        value: String,
+        expected: &'static str,
+    },
+}
+
+impl fmt::Display for QueryIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QueryIssue::UnknownFilter(filter) => write!(f, "Unknown filter {filter}"),
+            QueryIssue::PlanRestricted { filter, plan } => {
+                write!(f, "Filter {filter} isn't available on the {plan} plan")
+            }
+            QueryIssue::InvalidValue {
+                filter,
+                value,
+                expected,
+            } => write!(
+                f,
+                "Invalid value {value:?} for filter {filter}, expected {expected}"
+            ),
+        }
+    }
+}
+
+impl QueryValidator {
+    /// Validates against the given filter names, ex: the ones returned by `host_filters`.
+    pub fn new(filters: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self {
+            filters: filters.into_iter().map(Into::into).collect(),
+            plan: None,
+            restricted: HashMap::new(),
+        }
+    }
+
+    /// The plan of the account, as found in `ApiInfoResponse::plan`, to check `restrict`ed
+    /// filters against.
+    pub fn plan(mut self, plan: impl Into<String>) -> Self {
+        self.plan = Some(plan.into());
+
+        self
+    }
+
+    /// Flags `filter` unless the plan is one of `plans`, replacing any earlier restriction of it.
+    /// Shodan doesn't publish a full list of which filters need which plan, `fetch` only
+    /// restricts the ones it documents.
+    pub fn restrict(
+        mut self,
+        filter: impl Into<String>,
+        plans: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        self.restricted
+            .insert(filter.into(), plans.into_iter().map(Into::into).collect());
+
+        self
+    }
+
+    /// Looks up the available filters and the plan of the account. Neither call costs credits.
+    /// `vuln` is restricted to academic and enterprise plans and `tag` to enterprise ones.
+    pub async fn fetch(client: &ShodanClient) -> Result<Self, Error> {
+        let filters = client.host_filters().await?;
+        let api_info = client.get_api_info().await?;
+
+        Ok(Self::new(filters)
+            .plan(api_info.plan)
+            .restrict("vuln", ["edu", "corp"])
+            .restrict("tag", ["corp"]))
+    }
+
+    pub fn issues(&self, query: &Query) -> Vec<QueryIssue> {
+        let mut issues = vec![];
+        for filter in query.filters() {
+            let name = filter.name.as_str();
+            if !self.filters.contains(name) {
+                issues.push(QueryIssue::UnknownFilter(name.to_string()));
+                continue;
+            }
+
+            if let (Some(plan), Some(plans)) = (&self.plan, self.restricted.get(name)) {
+                if !plans.contains(plan) {
+                    issues.push(QueryIssue::PlanRestricted {
+                        filter: name.to_string(),
+                        plan: plan.clone(),
+                    });
+                }
+            }
+
+            issues.extend(check_values(filter));
+        }
+
+        issues
+    }
+
+    /// Fails with `Error::QueryValidation` listing every issue found in the query.
+    pub fn validate(&self, query: &Query) -> Result<(), Error> {
+        let issues = self.issues(query);
+        if !issues.is_empty() {
+            return Err(Error::QueryValidation(issues));
+        }
+
+        Ok(())
+    }
+}
+
+fn check_values(filter: &QueryFilter) -> Vec<QueryIssue> {
+    let invalid = |value: &str, expected| QueryIssue::InvalidValue {
+        filter: filter.name.clone(),
+        value: value.to_string(),
+        expected,
+    };
+
+    let (check, expected): (fn(&str) -> bool, _) = match filter.name.as_str() {
+        "port" | "http.status" | "ssl.cert.pubkey.bits" | "ssl.cipher.bits" | "ssl.chain_count" => {
+            (|value| value.parse::<u32>().is_ok(), "a number")
+        }
+        "hash" | "http.favicon.hash" | "http.html_hash" | "http.headers_hash"
+        | "http.robots_hash" => (|value| value.parse::<i64>().is_ok(), "a number"),
+        "has_screenshot" | "has_ssl" | "has_ipv6" | "has_vuln" | "ssl.cert.expired" => {
+            (|value| matches!(value, "true" | "false"), "true or false")
+        }
+        "before" | "after" => (is_date, "a date formatted as dd/mm/yyyy"),
+        "ip" | "net" => (is_net, "an IP address or CIDR range"),
+        "geo" => (
+            |value| parse_geo(value).is_some(),
+            "latitude,longitude[,radius]",
+        ),
+        _ => return vec![],
+    };
+
+    filter
+        .values
+        .iter()
+        .filter(|value| !check(value))
+        .map(|value| invalid(value, expected))
+        .collect()
+}
+
+fn is_date(value: &str) -> bool {
+    let parts = value.split('/').collect::<Vec<_>>();
+    let [day, month, year] = parts[..] else {
+        return false;
+    };
+    if year.len() != 4 {
+        return false;
+    }
+
+    let parse = |part: &str| part.parse::<i64>().ok();
+    let (Some(day), Some(month), Some(year)) = (parse(day), parse(month), parse(year)) else {
+        return false;
+    };
+
+    timestamp::days_in_month(year, month).is_some_and(|days| (1..=days).contains(&day))
+}
+
+fn is_net(value: &str) -> bool {
+    value.parse::<IpNet>().is_ok() || value.parse::<IpAddr>().is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::MockServer;
+    use crate::*;
+
+    fn validator() -> QueryValidator {
+        QueryValidator::new(["port", "country", "vuln", "net", "geo", "after", "has_ssl"])
+            .plan("dev")
+            .restrict("vuln", ["edu", "corp"])
+    }
+
+    fn issues(query: &str) -> Vec<QueryIssue> {
+        validator().issues(&Query::parse(query).unwrap())
+    }
+
+    #[test]
+    fn accepts_valid_queries() {
+        assert!(
+            issues("apache port:80,443 -country:CN net:10.0.0.0/8 geo:52.37,4.89,50").is_empty()
+        );
+        assert!(issues("after:01/03/2023 has_ssl:true").is_empty());
+        assert!(issues("after:29/02/2024").is_empty());
+    }
+
+    #[test]
+    fn flags_impossible_dates() {
+        for date in ["99/99/2023", "31/04/2023", "29/02/2023", "00/01/2023"] {
+            assert_eq!(issues(&format!("after:{date}")).len(), 1, "{date}");
+        }
+    }
+
+    #[test]
+    fn only_restricts_configured_filters() {
+        let validator = QueryValidator::new(["vuln", "tag"]).plan("dev");
+        let query = Query::parse("vuln:CVE-2014-0160 tag:ics").unwrap();
+
+        assert!(validator.issues(&query).is_empty());
+        assert_eq!(
+            validator.restrict("tag", ["corp"]).issues(&query),
+            vec![QueryIssue::PlanRestricted {
+                filter: String::from("tag"),
+                plan: String::from("dev"),
+            }]
+        );
+    }
+
+    #[test]
+    fn flags_invalid_queries() {
+        assert_eq!(
+            issues("prot:22 vuln:CVE-2014-0160 port:http net:10.0.0.0/33 after:2023"),
+            vec![
+                QueryIssue::UnknownFilter(String::from("prot")),
+                QueryIssue::PlanRestricted {
+                    filter: String::from("vuln"),
+                    plan: String::from("dev"),
+                },
+                QueryIssue::InvalidValue {
+                    filter: String::from("port"),
+                    value: String::from("http"),
+                    expected: "a number",
+                },
+                QueryIssue::InvalidValue {
+                    filter: String::from("net"),
+                    value: String::from("10.0.0.0/33"),
+                    expected: "an IP address or CIDR range",
+                },
+                QueryIssue::InvalidValue {
+                    filter: String::from("after"),
+                    value: String::from("2023"),
+                    expected: "a date formatted as dd/mm/yyyy",
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn validates_builder() {
+        assert!(SearchQueryBuilder::default()
+            .port(22)
+            .validate(&validator())
+            .is_ok());
+        assert!(matches!(
+            SearchQueryBuilder::default().org("Google").validate(&validator()),
+            Err(Error::QueryValidation(issues)) if issues == vec![QueryIssue::UnknownFilter(String::from("org"))]
+        ));
+    }
+
+    #[tokio::test]
+    async fn validates_searches_before_sending() {
+        let server = MockServer::with_fixtures().await;
+        let client = ShodanClient::builder(testing::MOCK_API_KEY)
+            .base_url(server.url())
+            .validate_queries(true)
+            .build()
+            .unwrap();
+
+        let error = client
+            .host_search(String::from("apache prot:22"), None, None, None)
+            .await
+            .unwrap_err();
+        assert!(matches!(error, Error::QueryValidation(_)));
+
+        let error = client
+            .host_search(String::from("vuln:CVE-2014-0160"), None, None, None)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            Error::QueryValidation(issues) if issues == vec![QueryIssue::PlanRestricted {
+                filter: String::from("vuln"),
+                plan: String::from("dev"),
+            }]
+        ));
+
+        client
+            .host_search(String::from("apache port:22"), None, None, None)
+            .await
+            .unwrap();
+
+        // The filters and plan are only looked up once
+        let paths = server
+            .received_requests()
+            .into_iter()
+            .map(|request| request.path)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            vec![
+                "/shodan/host/search/filters",
+                "/api-info",
+                "/shodan/host/search"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn validates_searches_with_configured_validator() {
+        let server = MockServer::with_fixtures().await;
+        let client = ShodanClient::builder(testing::MOCK_API_KEY)
+            .base_url(server.url())
+            .query_validator(validator().restrict("port", ["corp"]))
+            .build()
+            .unwrap();
+
+        let error = client
+            .host_search(String::from("apache port:22"), None, None, None)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            Error::QueryValidation(issues) if issues == vec![QueryIssue::PlanRestricted {
+                filter: String::from("port"),
+                plan: String::from("dev"),
+            }]
+        ));
+        assert!(server.received_requests().is_empty());
+    }
+}